@@ -0,0 +1,217 @@
+// a blocking client: one connection per request, like the cli. timeouts cover the whole conversation after connecting,
+// so a wedged daemon can't hang whoever's asking (event streams wait for as long as it takes, on purpose)
+use serde::de::DeserializeOwned;
+
+use std::io;
+use std::os::unix::net::UnixStream;
+use std::time::Duration;
+
+use crate::commands::{HistoryCommand, Query, Request};
+use crate::events::Event;
+use crate::sockets::{client_hello, default_socket_name, read_frame, read_response_from_daemon_socket, socket_addr, write_frame, ErrorCode, Response};
+use crate::status::{CacheStats, DiskCacheUsage, HistoryEntry, ImagesStatus, OutputStatus, Status, ThreadStatus, WorkspaceStatus};
+
+#[derive(Debug)]
+pub enum Error {
+    // couldn't connect, or the connection broke/timed out
+    Io(io::Error),
+    // the daemon understood and said no
+    Daemon { code: ErrorCode, message: String },
+    // the daemon answered with something we can't make sense of (usually a version mismatch in disguise)
+    Decode(serde_json::Error),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "{e}"),
+            Error::Daemon { message, .. } => write!(f, "{message}"),
+            Error::Decode(e) => write!(f, "unexpected answer from the daemon: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Error {
+        Error::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(err: serde_json::Error) -> Error {
+        Error::Decode(err)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Client {
+    socket: String,
+    timeout: Option<Duration>,
+}
+
+impl Default for Client {
+    fn default() -> Client {
+        Client::with_socket(default_socket_name())
+    }
+}
+
+impl Client {
+    // the session's daemon: $PANDORA_SOCKET, or the name derived from the uid and $WAYLAND_DISPLAY
+    pub fn new() -> Client {
+        Client::default()
+    }
+
+    // an abstract socket name, or a path if it has a slash in it
+    pub fn with_socket(socket: impl Into<String>) -> Client {
+        Client { socket: socket.into(), timeout: Some(Duration::from_secs(5)) }
+    }
+
+    // None waits forever. loading a big image can take a while, so don't go too low
+    pub fn timeout(mut self, timeout: Option<Duration>) -> Client {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn socket(&self) -> &str {
+        &self.socket
+    }
+
+    fn connect(&self, request: &Request) -> Result<(UnixStream, Response), Error> {
+        let stream = UnixStream::connect_addr(&socket_addr(&self.socket)?)?;
+        stream.set_read_timeout(self.timeout)?;
+        stream.set_write_timeout(self.timeout)?;
+        if let Response::Err { code, message } = client_hello(&stream)? {
+            return Err(Error::Daemon { code, message });
+        }
+        write_frame(&stream, request)?;
+        let response = read_response_from_daemon_socket(&stream)?;
+        Ok((stream, response))
+    }
+
+    // the raw payload: a string for commands that just get done, structured json for the rest
+    pub fn send(&self, request: &Request) -> Result<serde_json::Value, Error> {
+        match self.connect(request)?.1 {
+            Response::Ok(payload) => Ok(payload),
+            Response::Err { code, message } => Err(Error::Daemon { code, message }),
+        }
+    }
+
+    fn send_typed<T: DeserializeOwned>(&self, request: &Request) -> Result<T, Error> {
+        Ok(serde_json::from_value(self.send(request)?)?)
+    }
+
+    pub fn status(&self) -> Result<Status, Error> {
+        self.send_typed(&Request::Query(Query::Status))
+    }
+
+    pub fn outputs(&self) -> Result<Vec<OutputStatus>, Error> {
+        self.send_typed(&Request::Query(Query::Outputs))
+    }
+
+    pub fn workspaces(&self) -> Result<Vec<WorkspaceStatus>, Error> {
+        self.send_typed(&Request::Query(Query::Workspaces))
+    }
+
+    pub fn threads(&self) -> Result<Vec<ThreadStatus>, Error> {
+        self.send_typed(&Request::Query(Query::Threads))
+    }
+
+    pub fn images(&self) -> Result<ImagesStatus, Error> {
+        self.send_typed(&Request::Query(Query::Images))
+    }
+
+    // newest first
+    pub fn history(&self, output: &str) -> Result<Vec<HistoryEntry>, Error> {
+        self.send_typed(&Request::History(HistoryCommand { output: output.to_string() }))
+    }
+
+    pub fn cache_stats(&self) -> Result<CacheStats, Error> {
+        self.send_typed(&Request::CacheStats)
+    }
+
+    // what was removed
+    pub fn cache_clear(&self) -> Result<DiskCacheUsage, Error> {
+        self.send_typed(&Request::CacheClear)
+    }
+
+    // blocks between events; ends when the daemon goes away or cuts us off for not keeping up
+    pub fn events(&self) -> Result<Events, Error> {
+        let (stream, response) = self.connect(&Request::EventStream)?;
+        if let Response::Err { code, message } = response {
+            return Err(Error::Daemon { code, message });
+        }
+        stream.set_read_timeout(None)?;
+        Ok(Events { stream })
+    }
+}
+
+pub struct Events {
+    stream: UnixStream,
+}
+
+impl Iterator for Events {
+    type Item = Result<Event, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match read_frame(&self.stream) {
+            Ok(event) => Some(Ok(event)),
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => None,
+            Err(e) => Some(Err(e.into())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sockets::{accept_client_hello, read_command_from_client_socket, write_response_to_client_socket};
+    use std::os::unix::net::UnixListener;
+    use std::thread;
+
+    // a daemon that answers `answers` connections with whatever `answer` says, then goes away
+    fn fake_daemon(name: &str, answers: usize, answer: fn(Request) -> Option<Response>) -> thread::JoinHandle<()> {
+        let listener = UnixListener::bind_addr(&socket_addr(name).unwrap()).unwrap();
+        thread::spawn(move || {
+            for stream in listener.incoming().take(answers) {
+                let stream = stream.unwrap();
+                accept_client_hello(&stream).unwrap();
+                let request = read_command_from_client_socket(&stream).unwrap();
+                if let Some(response) = answer(request) {
+                    write_response_to_client_socket(&response, &stream).unwrap();
+                    if let Response::Ok(serde_json::Value::String(s)) = &response && s == "streaming" {
+                        write_frame(&stream, &Event::Locked).unwrap();
+                    }
+                } else {
+                    thread::sleep(Duration::from_millis(300));
+                }
+            }
+        })
+    }
+
+    #[test]
+    fn talks_to_a_daemon() {
+        let name = format!("pandora-ipc-test-{}", std::process::id());
+        let daemon = fake_daemon(&name, 4, |request| match request {
+            Request::CacheStats => Some(Response::Ok(serde_json::to_value(CacheStats::default()).unwrap())),
+            Request::EventStream => Some(Response::ok("streaming")),
+            Request::Lock => None, // never answers
+            _ => Some(Response::err(ErrorCode::NotFound, "no output named DP-9")),
+        });
+        let client = Client::with_socket(&name).timeout(Some(Duration::from_millis(50)));
+
+        assert_eq!(client.cache_stats().unwrap(), CacheStats::default());
+        match client.send(&Request::Reload) {
+            Err(Error::Daemon { code: ErrorCode::NotFound, message }) => assert_eq!(message, "no output named DP-9"),
+            other => panic!("{other:?}"),
+        }
+        assert_eq!(client.events().unwrap().next().unwrap().unwrap(), Event::Locked);
+        match client.send(&Request::Lock) {
+            Err(Error::Io(e)) => assert!(matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut)),
+            other => panic!("{other:?}"),
+        }
+        daemon.join().unwrap();
+        assert!(matches!(client.send(&Request::Lock), Err(Error::Io(_))));
+    }
+}
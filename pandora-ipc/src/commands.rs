@@ -0,0 +1,149 @@
+use serde::{Serialize, Deserialize};
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Copy, Clone, Debug)]
+#[cfg_attr(feature = "knuffel", derive(knuffel::DecodeScalar))]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+pub enum RenderMode {
+    // single image
+    Static, // will scale up/down to fill
+    ScrollVertical,
+    ScrollLateral,
+    // scrolling both directions will be trickier to implement. later problem.
+    // hello from later me: honestly it's probably easier than I thought:
+    // the agent can enforce positional state well, & correcting-on-the-fly looks better than expected
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct LoadImageCommand {
+    pub image: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct RenderCommand {
+    pub output: String,
+    pub image: String,
+    pub mode: RenderMode,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "clap", derive(clap::Args))]
+pub struct StopCommand {
+    pub output: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ScrollCommand {
+    pub output: String,
+    pub position: u32,
+}
+
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SlideshowAction {
+    Next,
+    Prev,
+    Pause, // toggles
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct SlideshowCommand {
+    pub output: Option<String>, // None => every output with a slideshow
+    pub action: SlideshowAction,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ResetCommand {
+    pub output: Option<String>, // None => every output
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct HistoryCommand {
+    pub output: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct UndoCommand {
+    pub output: String,
+}
+
+// what `Request::Query` can ask about; answered with the matching type from `status`
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+pub enum Query {
+    Status, // all of the below at once
+    Outputs,
+    Workspaces,
+    Threads,
+    Images,
+    Config,
+}
+
+// everything a client can ask of the daemon, one per connection (after the handshake, see `sockets`)
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum Request {
+    Stop, // the whole daemon
+    Lock,
+    Reload, // re-read the config file, even if it hasn't changed
+    LoadImage(LoadImageCommand),
+    Render(RenderCommand),
+    Scroll(ScrollCommand),
+    StopThread(StopCommand),
+    Slideshow(SlideshowCommand),
+    // forget what set/scroll/next/prev/pause changed and go back to what the config says
+    Reset(ResetCommand),
+    // what an output has shown lately, answered with a list of status::HistoryEntry
+    History(HistoryCommand),
+    // back to whatever an output showed before its last change
+    Undo(UndoCommand),
+    CacheStats,
+    // empties the daemon's disk cache; answered with the status::DiskCacheUsage that was removed
+    CacheClear,
+    Query(Query),
+    // keeps the connection open and sends events::Event frames until the client goes away
+    EventStream,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::Event;
+    use crate::sockets::{ErrorCode, Response};
+
+    fn round_trip<T: Serialize + serde::de::DeserializeOwned + PartialEq + std::fmt::Debug>(value: T) {
+        let json = serde_json::to_string(&value).unwrap();
+        assert_eq!(serde_json::from_str::<T>(&json).unwrap(), value, "{json}");
+    }
+
+    #[test]
+    fn everything_on_the_wire_round_trips() {
+        let render = RenderCommand { output: "DP-1".to_string(), image: "/a.png".to_string(), mode: RenderMode::ScrollVertical };
+        for request in [
+            Request::Stop,
+            Request::Lock,
+            Request::Reload,
+            Request::LoadImage(LoadImageCommand { image: "/a.png".to_string() }),
+            Request::Render(render.clone()),
+            Request::Scroll(ScrollCommand { output: "DP-1".to_string(), position: 420 }),
+            Request::StopThread(StopCommand { output: "DP-1".to_string() }),
+            Request::Slideshow(SlideshowCommand { output: None, action: SlideshowAction::Pause }),
+            Request::Reset(ResetCommand { output: Some("DP-1".to_string()) }),
+            Request::History(HistoryCommand { output: "DP-1".to_string() }),
+            Request::Undo(UndoCommand { output: "DP-1".to_string() }),
+            Request::CacheStats,
+            Request::CacheClear,
+            Request::Query(Query::Workspaces),
+            Request::EventStream,
+        ] {
+            round_trip(request);
+        }
+        round_trip(Response::ok("done"));
+        round_trip(Response::err(ErrorCode::VersionMismatch, "nope"));
+        round_trip(Event::WallpaperChanged { output: render.output, image: render.image, mode: render.mode });
+        round_trip(crate::status::HistoryEntry { image: "/a.png".to_string(), mode: RenderMode::Static, at: 1760000000,
+            cause: crate::status::ChangeCause::Slideshow });
+        round_trip(Event::TransitionFinished { output: "DP-1".to_string(), position: 3 });
+        // the shape clients in other languages see
+        assert_eq!(serde_json::to_string(&Request::Query(Query::Status)).unwrap(), r#"{"Query":"Status"}"#);
+        assert_eq!(serde_json::to_string(&Response::err(ErrorCode::NotFound, "x")).unwrap(),
+            r#"{"Err":{"code":"not-found","message":"x"}}"#);
+    }
+}
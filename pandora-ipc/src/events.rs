@@ -0,0 +1,15 @@
+// what `Request::EventStream` sends, one frame per event
+use serde::{Serialize, Deserialize};
+
+use crate::commands::RenderMode;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum Event {
+    // a render thread put a new image up
+    WallpaperChanged { output: String, image: String, mode: RenderMode },
+    // a scroll animation came to rest
+    TransitionFinished { output: String, position: u32 },
+    Locked,
+    Unlocked, // for when the lockscreen learns to unlock; never sent yet
+    ImageFailed { image: String, message: String },
+}
@@ -0,0 +1,16 @@
+// everything needed to talk to a running pandora daemon: the requests it takes, what it answers, the events it
+// streams, and a blocking Client that does the socket work.
+//
+// stability: this crate follows semver on its own, separately from pandora. anything that changes what goes over the
+// wire (new requests and events included) bumps sockets::PROTOCOL_VERSION and, while we're 0.x, the crate's minor
+// version; fixes that leave the wire alone only bump the patch version. the daemon refuses clients speaking another
+// protocol version up front, so mismatches show up as ErrorCode::VersionMismatch instead of confusing decode errors
+pub mod client;
+pub mod commands;
+pub mod events;
+pub mod sockets;
+pub mod status;
+
+pub use client::{Client, Error};
+pub use commands::Request;
+pub use sockets::{ErrorCode, Response, PROTOCOL_VERSION};
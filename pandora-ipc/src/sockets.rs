@@ -0,0 +1,193 @@
+// the daemon's ipc protocol. every message either way is a frame: a little-endian u32 byte count, then that much json.
+// a connection goes:
+//   client -> Hello { version }     daemon -> Response (Ok with the daemon's Hello, or Err VersionMismatch)
+//   client -> Request               daemon -> Response
+// (an EventStream's Ok response is followed by one events::Event frame per event, until either side hangs up)
+// bump PROTOCOL_VERSION whenever Request or Response change shape, so old clients fail loudly instead of weirdly
+use crate::commands::Request;
+
+use serde::{Serialize, Deserialize};
+use serde::de::DeserializeOwned;
+
+use std::io::{Error, ErrorKind, Read, Write};
+use std::path::Path;
+use std::os::linux::net::SocketAddrExt;
+use std::os::fd::AsRawFd;
+use std::os::unix::net::{SocketAddr, UnixStream};
+
+pub const PROTOCOL_VERSION: u32 = 7;
+// nothing legitimate comes close; stops a garbage length from allocating the world
+const MAX_FRAME_BYTES: u32 = 64 * 1024 * 1024;
+
+// abstract names are shared by everything in the network namespace, so each session (and each nested compositor)
+// gets its own: PANDORA_SOCKET if it's set, otherwise one made from the uid and WAYLAND_DISPLAY
+pub fn default_socket_name() -> String {
+    if let Ok(name) = std::env::var("PANDORA_SOCKET") && !name.is_empty() {
+        return name;
+    }
+    let uid = unsafe { libc::getuid() };
+    socket_name_for(uid, std::env::var("WAYLAND_DISPLAY").ok().as_deref())
+}
+
+fn socket_name_for(uid: u32, wayland_display: Option<&str>) -> String {
+    // WAYLAND_DISPLAY is allowed to be a full path to the compositor's socket
+    let display = wayland_display
+        .and_then(|d| Path::new(d).file_name())
+        .map(|d| d.to_string_lossy().to_string())
+        .filter(|d| !d.is_empty());
+    match display {
+        Some(display) => format!("pandora-{uid}-{display}"),
+        None => format!("pandora-{uid}"),
+    }
+}
+
+// who's on the other end, straight from the kernel (SO_PEERCRED), so it can't be faked by the client
+pub fn peer_uid(socket: &UnixStream) -> Result<u32, Error> {
+    let mut cred = libc::ucred { pid: 0, uid: 0, gid: 0 };
+    let mut len = std::mem::size_of::<libc::ucred>() as libc::socklen_t;
+    let ret = unsafe {
+        libc::getsockopt(socket.as_raw_fd(), libc::SOL_SOCKET, libc::SO_PEERCRED,
+            &mut cred as *mut libc::ucred as *mut libc::c_void, &mut len)
+    };
+    if ret != 0 {
+        return Err(Error::last_os_error());
+    }
+    Ok(cred.uid)
+}
+
+// anything with a slash in it is a socket file (say, under $XDG_RUNTIME_DIR); everything else an abstract name
+pub fn socket_addr(name: &str) -> Result<SocketAddr, Error> {
+    if name.contains('/') {
+        SocketAddr::from_pathname(name)
+    } else {
+        SocketAddr::from_abstract_name(name)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct Hello {
+    pub version: u32,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ErrorCode {
+    BadRequest, // couldn't read a frame/command out of what was sent
+    VersionMismatch,
+    NotFound, // no such output, thread, file
+    InvalidArgument, // understood, but can't be done (e.g. scrolling a static output)
+    Image, // couldn't decode or rasterize it
+    MissingDecoder, // this build can't decode that format
+    Config,
+    PermissionDenied, // the connecting user isn't allowed to talk to this daemon
+    Internal, // our own fault: poisoned locks, io trouble
+}
+
+// what the daemon says back to everything. payloads are json: a plain string for "done that", structured for queries
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum Response {
+    Ok(serde_json::Value),
+    Err { code: ErrorCode, message: String },
+}
+
+impl Response {
+    pub fn ok(message: impl Into<String>) -> Response {
+        Response::Ok(serde_json::Value::String(message.into()))
+    }
+
+    pub fn err(code: ErrorCode, message: impl Into<String>) -> Response {
+        Response::Err { code, message: message.into() }
+    }
+}
+
+pub fn write_frame<T: Serialize>(mut socket: &UnixStream, payload: &T) -> Result<(), Error> {
+    let serialized = serde_json::to_vec(payload).map_err(|e| Error::new(ErrorKind::InvalidInput, e))?;
+    let length = u32::try_from(serialized.len()).ok().filter(|l| *l <= MAX_FRAME_BYTES)
+        .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "message too big to send"))?;
+    socket.write_all(&length.to_le_bytes())?;
+    socket.write_all(&serialized)
+}
+
+pub fn read_frame<T: DeserializeOwned>(mut socket: &UnixStream) -> Result<T, Error> {
+    let mut length = [0u8; 4];
+    socket.read_exact(&mut length)?;
+    let length = u32::from_le_bytes(length);
+    if length > MAX_FRAME_BYTES {
+        return Err(Error::new(ErrorKind::InvalidData, format!("frame of {length} bytes is over the limit")));
+    }
+    let mut buf: Vec<u8> = vec![0; length as usize];
+    socket.read_exact(buf.as_mut_slice())?;
+    serde_json::from_slice(&buf).map_err(|e| Error::new(ErrorKind::InvalidData, e))
+}
+
+// client side of the handshake; a Response::Err means the daemon won't talk to us
+pub fn client_hello(socket: &UnixStream) -> Result<Response, Error> {
+    write_frame(socket, &Hello { version: PROTOCOL_VERSION })?;
+    read_response_from_daemon_socket(socket)
+}
+
+pub fn read_response_from_daemon_socket(socket: &UnixStream) -> Result<Response, Error> {
+    read_frame(socket)
+}
+
+pub fn write_response_to_client_socket(response: &Response, socket: &UnixStream) -> Result<(), Error> {
+    write_frame(socket, response)
+}
+
+// daemon side of the handshake. the client's told why on a mismatch; the Err is for the daemon to log
+pub fn accept_client_hello(socket: &UnixStream) -> Result<(), Error> {
+    let hello: Hello = match read_frame(socket) {
+        Ok(hello) => hello,
+        Err(e) => {
+            _ = write_response_to_client_socket(&Response::err(ErrorCode::BadRequest, format!("expected a hello: {e}")), socket);
+            return Err(e);
+        },
+    };
+    if hello.version != PROTOCOL_VERSION {
+        let message = format!("daemon speaks protocol version {PROTOCOL_VERSION}, client speaks {}", hello.version);
+        _ = write_response_to_client_socket(&Response::err(ErrorCode::VersionMismatch, message.clone()), socket);
+        return Err(Error::new(ErrorKind::InvalidData, message));
+    }
+    let ours = serde_json::to_value(Hello { version: PROTOCOL_VERSION }).map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+    write_response_to_client_socket(&Response::Ok(ours), socket)
+}
+
+pub fn read_command_from_client_socket(socket: &UnixStream) -> Result<Request, Error> {
+    read_frame(socket)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames_round_trip_and_reject_garbage() {
+        let (a, b) = UnixStream::pair().unwrap();
+        write_frame(&a, &Request::Lock).unwrap();
+        assert_eq!(read_command_from_client_socket(&b).unwrap(), Request::Lock);
+
+        let response = Response::err(ErrorCode::NotFound, "no output named DP-9");
+        write_response_to_client_socket(&response, &b).unwrap();
+        assert_eq!(read_response_from_daemon_socket(&a).unwrap(), response);
+
+        // a length nobody would send, then json that isn't a command
+        (&a).write_all(&u32::MAX.to_le_bytes()).unwrap();
+        assert_eq!(read_command_from_client_socket(&b).unwrap_err().kind(), ErrorKind::InvalidData);
+        write_frame(&a, &"not a command").unwrap();
+        assert_eq!(read_command_from_client_socket(&b).unwrap_err().kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn peer_is_us() {
+        let (a, _b) = UnixStream::pair().unwrap();
+        assert_eq!(peer_uid(&a).unwrap(), unsafe { libc::getuid() });
+    }
+
+    #[test]
+    fn socket_names_follow_the_session() {
+        assert_eq!(socket_name_for(1000, Some("wayland-1")), "pandora-1000-wayland-1");
+        assert_eq!(socket_name_for(1000, Some("/run/user/1000/wayland-2")), "pandora-1000-wayland-2");
+        assert_eq!(socket_name_for(1001, None), "pandora-1001");
+        assert_eq!(socket_name_for(1001, Some("")), "pandora-1001");
+    }
+}
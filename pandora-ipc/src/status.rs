@@ -0,0 +1,153 @@
+// snapshots of what the daemon is up to, as answers to `Request::Query` and `Request::CacheStats`. all plain data,
+// serialized straight to json for bars and scripts
+use serde::{Serialize, Deserialize};
+
+use crate::commands::RenderMode;
+
+// an output's position and size in the compositor's global space (xdg-output, or niri's Output.logical)
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LogicalRect {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct OutputStatus {
+    pub name: String,
+    pub size: Option<(u32, u32)>, // current mode, physical pixels
+    pub layout: Option<LogicalRect>,
+    pub group: Option<String>,
+    pub image: Option<String>, // whatever its render thread was last told to show
+    pub mode: Option<RenderMode>,
+    pub slideshow: Option<SlideshowStatus>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct SlideshowStatus {
+    pub paused: bool,
+    pub next_in_secs: u64, // 0 when paused
+}
+
+// straight from niri, plus the image the config gives a workspace of that name (if any)
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct WorkspaceStatus {
+    pub id: u64,
+    pub idx: u8,
+    pub name: Option<String>,
+    pub output: Option<String>,
+    pub active: bool,
+    pub focused: bool,
+    pub image: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ThreadStatus {
+    pub output: String,
+    pub alive: bool,
+    pub render: Option<RenderStatus>, // None until the thread has drawn something
+}
+
+// the interesting bits of a render thread's state, published by the thread whenever they change
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RenderStatus {
+    pub image: String,
+    pub mode: RenderMode,
+    pub width: u32, // what's visible on the output, in buffer pixels
+    pub height: u32,
+    pub image_width: u32, // the whole scaled image
+    pub image_height: u32,
+    pub frames: usize, // 1 for stills
+    pub scroll_position: Option<u32>, // where the last scroll was headed
+    pub layers: usize,
+}
+
+// one decoded image
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ImageEntry {
+    pub path: String,
+    pub width: u32,
+    pub height: u32,
+    pub bytes: u64,
+    pub pinned: bool,
+}
+
+// one scaled buffer
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct BufferEntry {
+    pub image: String,
+    pub width: u32,
+    pub height: u32,
+    pub frames: usize,
+    pub bytes: u64,
+    pub users: usize,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ImagesStatus {
+    pub decoded: Vec<ImageEntry>, // full-size originals in memory
+    pub buffers: Vec<BufferEntry>, // scaled, shared with the compositor
+}
+
+// everything at once, for `pandora status`. the config is left as plain json: its shape follows pandora's config
+// file, not this crate's versioning
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Status {
+    pub outputs: Vec<OutputStatus>,
+    pub workspaces: Vec<WorkspaceStatus>,
+    pub threads: Vec<ThreadStatus>,
+    pub images: ImagesStatus,
+    pub config: serde_json::Value,
+}
+
+// why an output's image changed
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ChangeCause {
+    Config, // startup, a config reload, a reset
+    Set,
+    Slideshow,
+    Schedule,
+}
+
+// one change in what an output shows, as answered to `Request::History` (newest first)
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub image: String,
+    pub mode: RenderMode,
+    pub at: u64, // unix seconds
+    pub cause: ChangeCause,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ImageCacheStats {
+    pub entries: usize,
+    pub bytes: u64,
+    pub budget_bytes: u64,
+    pub pinned: Vec<String>,
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct BufferCacheStats {
+    pub entries: usize,
+    pub bytes: u64,
+    pub users: usize,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct DiskCacheUsage {
+    pub files: usize,
+    pub bytes: u64,
+}
+
+// everything `pandora cache stats` reports
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct CacheStats {
+    pub images: ImageCacheStats,
+    pub buffers: BufferCacheStats,
+    pub disk: DiskCacheUsage,
+    pub disk_limit_bytes: u64,
+}
@@ -1,15 +1,28 @@
-use ::pandora::pithos::config::LogLevel;
-use ::pandora::pithos::commands::{CommandType, DaemonCommand, StopCommand, RenderThreadCommand};
+use ::pandora::pithos::config::{load_config, LogLevel};
+use pandora_ipc::commands::{HistoryCommand, LoadImageCommand, Query, RenderCommand, RenderMode, ResetCommand, ScrollCommand, SlideshowAction, SlideshowCommand, StopCommand, UndoCommand};
+use pandora_ipc::sockets::default_socket_name;
+use pandora_ipc::status::Status;
+use pandora_ipc::{Client, Error, Request};
 use clap::{arg, Parser};
 use std::process;
 
+// exit statuses: 0 when the daemon did what it was asked, 1 when it (or the config) said no, 2 when it couldn't be
+// reached at all
+const EXIT_REFUSED: i32 = 1;
+const EXIT_NO_DAEMON: i32 = 2;
+
 #[derive(Parser)]
 #[command(name = "pandora")]
 #[command(about = "a parallax wallpaper and lockscreen daemon for Wayland")]
 #[command(version)]
+#[command(after_help = "exit status is 0 on success, 1 if the daemon refused the command and 2 if it isn't running")]
 struct Interface {
     #[arg(long="log-level")]
     log_level: Option<LogLevel>,
+    /// the ipc socket to listen on or talk to: an abstract name, or a path. defaults to $PANDORA_SOCKET, or one made
+    /// from your uid and $WAYLAND_DISPLAY
+    #[arg(long, global = true)]
+    socket: Option<String>,
     #[command(subcommand)]
     command: Option<CliCommand>,
 }
@@ -19,18 +32,252 @@ enum CliCommand {
     StopDaemon,
     StopThread(StopCommand),
     Lock,
+    #[command(subcommand)]
+    Cache(CacheCommand),
+    /// skip to the next slideshow image
+    Next(SlideshowTarget),
+    /// go back to the previous slideshow image
+    Prev(SlideshowTarget),
+    /// pause (or resume) slideshows
+    Pause(SlideshowTarget),
+    /// show an image on an output until something else replaces it
+    Set {
+        output: String,
+        image: String,
+        /// defaults to the output's mode in the config, or static
+        #[arg(long, value_enum)]
+        mode: Option<RenderMode>,
+    },
+    /// jump a scrolling output to a position, in pixels along the image
+    Scroll {
+        output: String,
+        position: u32,
+    },
+    /// re-read the config file now
+    Reload,
+    /// forget what set, scroll, next, prev and pause changed and go back to the config
+    Reset {
+        /// only this output; every output otherwise
+        output: Option<String>,
+    },
+    /// decode an image into the daemon's cache ahead of time
+    Preload {
+        image: String,
+    },
+    /// what the daemon is showing where
+    Status {
+        /// everything the daemon knows, as json
+        #[arg(long)]
+        json: bool,
+    },
+    /// ask the daemon about one thing, answered in json
+    Query {
+        #[arg(value_enum)]
+        what: Query,
+    },
+    /// print the daemon's events as they happen, one json object per line
+    Events,
+    /// what an output has shown lately, newest first
+    History {
+        output: String,
+        #[arg(long)]
+        json: bool,
+    },
+    /// go back to what an output showed before its last change
+    Undo {
+        output: String,
+    },
+}
+
+#[derive(Clone, clap::Args)]
+struct SlideshowTarget {
+    /// only this output; every output with a slideshow otherwise
+    output: Option<String>,
+}
+
+#[derive(Clone, clap::Subcommand)]
+enum CacheCommand {
+    /// delete every pre-scaled image from the on-disk cache
+    Clear,
+    /// show memory and disk usage of the daemon's image caches
+    Stats,
+}
+
+// exits with the status matching whatever went wrong
+fn fail(client: &Client, err: Error) -> ! {
+    match err {
+        Error::Io(e) => {
+            eprintln!("could not reach the daemon on {} (is it running?): {e}", client.socket());
+            process::exit(EXIT_NO_DAEMON);
+        },
+        e => {
+            eprintln!("{e}");
+            process::exit(EXIT_REFUSED);
+        },
+    }
+}
+
+// send a request and hand back what the daemon answered, exiting with the matching status if it didn't work out
+fn request(client: &Client, request: &Request) -> serde_json::Value {
+    client.send(request).unwrap_or_else(|e| fail(client, e))
 }
 
-pub fn cli() -> Option<LogLevel> { // the only config pass-able to the daemon via cli
+// strings are messages, anything else is structured and gets printed as json
+fn send(client: &Client, req: &Request) -> ! {
+    match request(client, req) {
+        serde_json::Value::String(msg) => println!("{msg}"),
+        payload => println!("{}", serde_json::to_string_pretty(&payload).expect("could not print response")),
+    }
+    process::exit(0);
+}
+
+fn mib(bytes: u64) -> u64 {
+    bytes / 1024 / 1024
+}
+
+fn print_status(status: &Status) {
+    for output in &status.outputs {
+        let size = output.size.map_or("?".to_string(), |(w, h)| format!("{w}x{h}"));
+        let mode = output.mode.map_or("-".to_string(), |m| format!("{m:?}").to_lowercase());
+        let mut extra = Vec::new();
+        if let Some(group) = &output.group {
+            extra.push(format!("group {group}"));
+        }
+        match &output.slideshow {
+            Some(show) if show.paused => extra.push("slideshow paused".to_string()),
+            Some(show) => extra.push(format!("next image in {}s", show.next_in_secs)),
+            None => (),
+        }
+        let extra = if extra.is_empty() { String::new() } else { format!(" ({})", extra.join(", ")) };
+        println!("{} {size} {mode} {}{extra}", output.name, output.image.as_deref().unwrap_or("nothing"));
+    }
+    for workspace in &status.workspaces {
+        let marker = if workspace.focused { "*" } else if workspace.active { "+" } else { " " };
+        println!("{marker} workspace {} on {}{}", workspace.idx, workspace.output.as_deref().unwrap_or("?"),
+            workspace.name.as_ref().map_or(String::new(), |n| format!(" \"{n}\"")));
+    }
+    let alive = status.threads.iter().filter(|t| t.alive).count();
+    println!("{} render threads ({alive} alive)", status.threads.len());
+    println!("{} decoded images ({} MiB), {} scaled buffers ({} MiB)",
+        status.images.decoded.len(), mib(status.images.decoded.iter().map(|i| i.bytes).sum()),
+        status.images.buffers.len(), mib(status.images.buffers.iter().map(|b| b.bytes).sum()));
+}
+
+fn handle_status(client: &Client, json: bool) {
+    if json {
+        let payload = request(client, &Request::Query(Query::Status));
+        println!("{}", serde_json::to_string_pretty(&payload).expect("could not print response"));
+        return;
+    }
+    match client.status() {
+        Ok(status) => print_status(&status),
+        Err(e) => fail(client, e),
+    }
+}
+
+fn handle_history(client: &Client, output: &str, json: bool) {
+    if json {
+        let payload = request(client, &Request::History(HistoryCommand { output: output.to_string() }));
+        println!("{}", serde_json::to_string_pretty(&payload).expect("could not print response"));
+        return;
+    }
+    let history = client.history(output).unwrap_or_else(|e| fail(client, e));
+    for entry in history {
+        let at = chrono::DateTime::from_timestamp(entry.at as i64, 0)
+            .map_or("?".to_string(), |t| t.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M:%S").to_string());
+        let cause = format!("{:?}", entry.cause).to_lowercase();
+        let mode = format!("{:?}", entry.mode).to_lowercase();
+        println!("{at} {cause:<9} {mode:<14} {}", entry.image);
+    }
+}
+
+fn handle_events(client: &Client) {
+    let events = client.events().unwrap_or_else(|e| fail(client, e));
+    // runs until the daemon goes away (or cuts us off for not keeping up)
+    for event in events {
+        match event {
+            Ok(event) => println!("{}", serde_json::to_string(&event).expect("could not print event")),
+            Err(_) => break,
+        }
+    }
+}
+
+fn handle_cache_command(client: &Client, command: CacheCommand) {
+    match command {
+        CacheCommand::Stats => send(client, &Request::CacheStats),
+        CacheCommand::Clear => match client.cache_clear() {
+            Ok(usage) => println!("cleared {} cached images ({} MiB)", usage.files, usage.bytes / 1024 / 1024),
+            Err(e) => fail(client, e),
+        },
+    }
+}
+
+fn slideshow_cmd(target: SlideshowTarget, action: SlideshowAction) -> Request {
+    Request::Slideshow(SlideshowCommand { output: target.output, action })
+}
+
+// the daemon has its own working directory, so paths are made absolute here
+fn image_path(image: &str) -> String {
+    let expanded = shellexpand::full(image).map(|p| p.to_string()).unwrap_or_else(|_| image.to_string());
+    match std::path::absolute(&expanded) {
+        Ok(path) => path.to_string_lossy().to_string(),
+        Err(_) => expanded,
+    }
+}
+
+fn configured_mode(output: &str) -> RenderMode {
+    load_config().ok()
+        .and_then(|config| config.outputs.into_iter().find(|o| o.name == output))
+        .and_then(|o| o.mode)
+        .unwrap_or(RenderMode::Static)
+}
+
+// the only config pass-able to the daemon via cli
+pub struct DaemonArgs {
+    pub log_level: Option<LogLevel>,
+    pub socket: String,
+}
+
+pub fn cli() -> DaemonArgs {
     let cli = Interface::parse();
+    let socket = cli.socket.unwrap_or_else(default_socket_name);
     if let Some(command) = cli.command {
-        let cmd = match command {
-            CliCommand::StopDaemon => CommandType::Dc(DaemonCommand::Stop),
-            CliCommand::StopThread(c) => CommandType::Tc(RenderThreadCommand::Stop(c)),
-            CliCommand::Lock => CommandType::Dc(DaemonCommand::Lock),
+        let client = Client::with_socket(socket);
+        let req = match command {
+            CliCommand::StopDaemon => Request::Stop,
+            CliCommand::StopThread(c) => Request::StopThread(c),
+            CliCommand::Lock => Request::Lock,
+            CliCommand::Next(t) => slideshow_cmd(t, SlideshowAction::Next),
+            CliCommand::Prev(t) => slideshow_cmd(t, SlideshowAction::Prev),
+            CliCommand::Pause(t) => slideshow_cmd(t, SlideshowAction::Pause),
+            CliCommand::Set { output, image, mode } => {
+                let mode = mode.unwrap_or_else(|| configured_mode(&output));
+                Request::Render(RenderCommand { output, image: image_path(&image), mode })
+            },
+            CliCommand::Scroll { output, position } => Request::Scroll(ScrollCommand { output, position }),
+            CliCommand::Reload => Request::Reload,
+            CliCommand::Reset { output } => Request::Reset(ResetCommand { output }),
+            CliCommand::Preload { image } => Request::LoadImage(LoadImageCommand { image: image_path(&image) }),
+            CliCommand::Query { what } => Request::Query(what),
+            CliCommand::Undo { output } => Request::Undo(UndoCommand { output }),
+            CliCommand::History { output, json } => {
+                handle_history(&client, &output, json);
+                process::exit(0);
+            },
+            CliCommand::Events => {
+                handle_events(&client);
+                process::exit(0);
+            },
+            CliCommand::Status { json } => {
+                handle_status(&client, json);
+                process::exit(0);
+            },
+            CliCommand::Cache(c) => {
+                handle_cache_command(&client, c);
+                process::exit(0);
+            }
         };
-        println!("{}", ::pandora::pithos::sockets::write_command_to_daemon_socket(&cmd).expect("could not send command (is the daemon running?)"));
-        process::exit(0);
+        send(&client, &req);
     }
-    return cli.log_level;
-}
\ No newline at end of file
+    return DaemonArgs { log_level: cli.log_level, socket };
+}
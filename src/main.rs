@@ -5,10 +5,10 @@ use ::pandora::pithos::config::load_config;
 use std::sync::Arc;
 
 fn main() -> miette::Result<()> {
-    let cli_verbosity = cli::cli();
+    let args = cli::cli();
     let config = load_config()?;
 
-    let verbosity = match cli_verbosity {
+    let verbosity = match args.log_level {
         Some(level) => level,
         None => config.log_level,
     };
@@ -17,16 +17,20 @@ fn main() -> miette::Result<()> {
     let mut pandora = crate::pandora::Pandora::new(config.clone(), verbosity);
     // we initialize pandora mutably so that logging can be started
     //  => other threads have The Logging Abstraction available for the entirety of their runtime
-    let ipc = crate::threads::ipc::InboundCommandHandler::new();
+    let ipc = crate::threads::ipc::InboundCommandHandler::new(&args.socket);
     let outputs = crate::threads::outputs::OutputHandler::new(config.clone());
     let niri = crate::threads::niri::NiriAgent::new(config.clone());
     let config_watcher = crate::threads::config::ConfigWatcher::new();
+    let slideshows = crate::threads::slideshow::SlideshowHandler::new();
+    let scheduler = crate::threads::schedule::Scheduler::new();
 
     Arc::make_mut(&mut pandora).bind_threads(
         ipc.clone(),
         outputs.clone(),
         niri.clone(),
         config_watcher.clone(),
+        slideshows.clone(),
+        scheduler.clone(),
     );
 
     // give the subthreads a weak pointer now that we're done mutating pandora into some sort of daemon
@@ -4,24 +4,49 @@ use crate::threads::niri::NiriAgent;
 use crate::threads::outputs::OutputHandler;
 use crate::threads::ipc::InboundCommandHandler;
 use crate::threads::render::{RenderThread};
-use pandora::pithos::config::{DaemonConfig, LogLevel};
-use pandora::pithos::misc::get_new_image_dimensions;
-use pandora::pithos::commands::{CommandType, DaemonCommand, LoadImageCommand, RenderThreadCommand};
+use crate::threads::schedule::Scheduler;
+use crate::threads::slideshow::SlideshowHandler;
+use pandora::pithos::animation::{frame_delay, frames as animation_frames, is_animated};
+use pandora::pithos::cache::buffers::{BufferCache, BufferKey, SharedBuffer};
+use pandora::pithos::cache::disk::{get_cache_dir, DiskCache};
+use pandora::pithos::cache::images::ImageCache;
+use pandora::pithos::config::{DaemonConfig, Effects, LayerConfig, LogLevel, OutputConfig, ScheduleEntry};
+use pandora::pithos::misc::{get_new_image_dimensions, mode_scale_to};
+use pandora::pithos::commands::{CommandType, DaemonCommand, RenderCommand, RenderMode, RenderThreadCommand, ScrollCommand, SlideshowAction, SlideshowCommand};
+use pandora::pithos::config;
 use pandora::pithos::error::{CommandError, DaemonError};
-use pandora::pithos::sockets::write_response_to_client_socket;
+use pandora::pithos::events::Subscribers;
+use pandora::pithos::history::History;
+use pandora::pithos::packages::{closest_variant, gnome_schedule, is_gnome_background, is_kde_package, kde_variants};
+use pandora::pithos::schedule::current_entry;
+use pandora::pithos::slideshow::{list_images, Slideshow};
+use pandora::pithos::state::{get_state_file, OutputOverride, RuntimeState, SlideshowState};
+use pandora::pithos::color::ColorSpace;
+use pandora::pithos::decoders;
+use pandora::pithos::layout::{group_region, LogicalRect};
+use pandora::pithos::svg;
+use pandora::pithos::transform::Transform;
 use pandora::wayland::render_helpers::RenderThreadWaylandState;
+use pandora_ipc::commands::{Query, Request};
+use pandora_ipc::events::Event;
+use pandora_ipc::sockets::{accept_client_hello, read_command_from_client_socket, write_frame, write_response_to_client_socket, ErrorCode, Response};
+use pandora_ipc::status::{CacheStats, ChangeCause, DiskCacheUsage, HistoryEntry, ImagesStatus, OutputStatus, RenderStatus, SlideshowStatus, Status, ThreadStatus, WorkspaceStatus};
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fs::File;
 use std::os::unix::net::{UnixStream};
+use std::path::Path;
 use std::sync::{Arc, RwLock, Weak};
 use std::sync::mpsc::{channel, Sender};
 use std::thread::{self, JoinHandle};
-use std::time::Duration;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use image::imageops::FilterType;
-use image::{RgbaImage, ImageReader};
 use wayrs_client::Connection;
+use wayrs_client::protocol::wl_shm::Format;
+
+// part of the disk cache key, so changing this invalidates previously scaled images
+const SCALE_FILTER: FilterType = FilterType::Lanczos3;
 
 // daemon utility struct(s)
 pub struct ThreadHandle {
@@ -58,25 +83,81 @@ pub struct Pandora {
     outputs_thread: Option<Arc<OutputHandler>>,
     niri_ag_thread: Option<Arc<NiriAgent>>,
     configw_thread: Option<Arc<ConfigWatcher>>,
+    slides_thread: Option<Arc<SlideshowHandler>>,
+    sched_thread: Option<Arc<Scheduler>>,
     // key: output name
     threads: Arc<RwLock<HashMap<String, ThreadHandle>>>,
+    // key: output name. whatever each render thread was last told to show
+    renders: Arc<RwLock<HashMap<String, RenderCommand>>>,
+    // key: output name. only outputs whose image is a directory/glob
+    slideshows: Arc<RwLock<HashMap<String, Slideshow>>>,
+    // key: output name. what it's shown lately, see pithos::history
+    history: Arc<RwLock<HashMap<String, History>>>,
+    // what set/scroll/next/prev/pause changed since the config was read, mirrored to disk. see pithos::state
+    state: Arc<RwLock<RuntimeState>>,
+    // key: output name. what each render thread last reported about itself, for `pandora status`
+    render_status: Arc<RwLock<HashMap<String, RenderStatus>>>,
+    // everyone listening on an event stream
+    subscribers: Arc<RwLock<Subscribers>>,
+    // key: output name. current mode size, for picking between resolution variants of a wallpaper
+    output_sizes: Arc<RwLock<HashMap<String, (u32, u32)>>>,
+    // key: output name. where it sits in the compositor's layout, for cutting up output groups' images
+    output_layout: Arc<RwLock<HashMap<String, LogicalRect>>>,
+    // what surfaces can be tagged as (wp_color_management_v1). empty if the compositor doesn't do color management
+    color_spaces: Arc<RwLock<Vec<ColorSpace>>>,
+    // key: file path. the color space each image's pixels end up in, see pithos::color
+    image_colors: Arc<RwLock<HashMap<String, ColorSpace>>>,
     // key: file path
-    // useful central cache of loaded images for lockscreen etc
-    images: Arc<RwLock<HashMap<String, RgbaImage>>>,
+    // useful central cache of loaded images for lockscreen etc. bounded by config.cache.memory-limit-mb
+    images: Arc<RwLock<ImageCache>>,
+    // scaled, ready-to-attach pixel buffers shared between render threads
+    buffers: Arc<RwLock<BufferCache>>,
+    // pre-scaled buffers persisted across restarts/mode changes
+    disk_cache: Arc<RwLock<DiskCache>>,
     // agent: Arc<AgentHandler>,
     config: Arc<RwLock<DaemonConfig>>,
 }
 
 impl Pandora {
     pub fn new(config: DaemonConfig, verbosity: LogLevel) -> Arc<Pandora> {
+        let mut images = ImageCache::new(config.cache.memory_limit_bytes());
+        // locking should be instant, so lockscreen images never get evicted once loaded
+        for img in config.lockscreen_images() {
+            images.pin(&img);
+        }
+        let logger = LogThread::new(verbosity);
+        let state = RuntimeState::load(&get_state_file()).unwrap_or_else(|e| {
+            logger.log(LogLevel::DEFAULT, "pandora", format!("ignoring runtime state in {}: {e}", get_state_file().display()));
+            RuntimeState::default()
+        });
+        let mut slideshows = build_slideshows(&config, HashMap::new());
+        for (key, saved) in &state.slideshows {
+            if let Some(show) = slideshows.get_mut(key) {
+                show.resume(saved.image.clone(), saved.paused);
+            }
+        }
         return Arc::new(Pandora {
-            logger: LogThread::new(verbosity),
+            logger,
             cmd_ipc_thread: None,
             outputs_thread: None,
             niri_ag_thread: None,
             configw_thread: None,
+            slides_thread: None,
+            sched_thread: None,
             threads: Arc::new(RwLock::new(HashMap::<String, ThreadHandle>::new())),
-            images: Arc::new(RwLock::new(HashMap::<String, RgbaImage>::new())),
+            renders: Arc::new(RwLock::new(HashMap::<String, RenderCommand>::new())),
+            slideshows: Arc::new(RwLock::new(slideshows)),
+            history: Arc::new(RwLock::new(HashMap::new())),
+            state: Arc::new(RwLock::new(state)),
+            render_status: Arc::new(RwLock::new(HashMap::new())),
+            subscribers: Arc::new(RwLock::new(Subscribers::default())),
+            output_sizes: Arc::new(RwLock::new(HashMap::new())),
+            output_layout: Arc::new(RwLock::new(HashMap::new())),
+            color_spaces: Arc::new(RwLock::new(Vec::new())),
+            image_colors: Arc::new(RwLock::new(HashMap::new())),
+            images: Arc::new(RwLock::new(images)),
+            buffers: Arc::new(RwLock::new(BufferCache::default())),
+            disk_cache: Arc::new(RwLock::new(DiskCache::new(get_cache_dir(), config.cache.disk_limit_bytes()))),
             config: Arc::new(RwLock::new(config)),
         });
     }
@@ -96,34 +177,90 @@ impl Pandora {
         outputs: Arc<OutputHandler>,
         niri: Arc<NiriAgent>,
         config: Arc<ConfigWatcher>,
+        slides: Arc<SlideshowHandler>,
+        schedule: Arc<Scheduler>,
     ) -> &mut Self {
         self.cmd_ipc_thread = Some(ipc);
         self.outputs_thread = Some(outputs);
         self.niri_ag_thread = Some(niri);
         self.configw_thread = Some(config);
+        self.slides_thread = Some(slides);
+        self.sched_thread = Some(schedule);
         return self;
     }
 
     fn reload_config(&self, cmd: &DaemonCommand) {
-        {
+        if let DaemonCommand::ReloadConfig(new_config) = cmd {
             match self.config.write() {
-                Ok(_conf) => {
+                Ok(mut conf) => {
                     // daemon should cache the current config so the lockscreen has current config cloned when it spawns
+                    self.repin_lockscreen_images(&conf, new_config);
+                    *conf = new_config.clone();
                 }
-                Err(_e) => {
-
+                Err(e) => self.log("pandora", format!("could not store reloaded config: {e:?}")),
+            }
+            match self.images.write() {
+                Ok(mut images) => {
+                    let evicted = images.set_budget(new_config.cache.memory_limit_bytes());
+                    self.log_evictions(&evicted);
+                }
+                Err(e) => self.log("pandora", format!("could not update image cache budget: {e:?}")),
+            }
+            match self.disk_cache.write() {
+                Ok(mut disk_cache) => {
+                    disk_cache.set_max_bytes(new_config.cache.disk_limit_bytes());
+                    if let Err(e) = disk_cache.enforce_limit() {
+                        self.log("pandora", format!("could not trim disk cache: {e:?}"));
+                    }
+                }
+                Err(e) => self.log("pandora", format!("could not update disk cache limit: {e:?}")),
+            }
+            match self.slideshows.write() {
+                Ok(mut slideshows) => {
+                    let old = std::mem::take(&mut *slideshows);
+                    *slideshows = build_slideshows(new_config, old);
                 }
+                Err(e) => self.log("pandora", format!("could not update slideshows: {e:?}")),
             }
         }
         // if sending to the other perpetual-threads fails i am assuming shit's fucked for other reasons
         let _ = self.outputs_thread.as_ref().unwrap().queue.send(cmd.clone());
         let _ = self.niri_ag_thread.as_ref().unwrap().queue.send(cmd.clone());
+        let _ = self.slides_thread.as_ref().unwrap().queue.send(cmd.clone());
+        let _ = self.sched_thread.as_ref().unwrap().queue.send(cmd.clone());
+    }
+
+    pub fn config(&self) -> Result<DaemonConfig, DaemonError> {
+        Ok(self.config.read()?.clone())
+    }
+
+    fn repin_lockscreen_images(&self, old: &DaemonConfig, new: &DaemonConfig) {
+        match self.images.write() {
+            Ok(mut images) => {
+                for img in new.lockscreen_images() {
+                    images.pin(&img);
+                }
+                for img in old.lockscreen_images() {
+                    let evicted = images.unpin(&img);
+                    self.log_evictions(&evicted);
+                }
+            },
+            Err(e) => self.log("pandora", format!("could not re-pin lockscreen images: {e:?}")),
+        }
+    }
+
+    fn log_evictions(&self, evicted: &Vec<String>) {
+        for img in evicted {
+            self.verbose("pandora", format!("evicted {img} from image cache"));
+        }
     }
 
     pub fn start(&self, weak: Weak<Pandora> ) {
         self.outputs_thread.as_ref().unwrap().start(weak.clone());
         self.niri_ag_thread.as_ref().unwrap().start(weak.clone());
         self.configw_thread.as_ref().unwrap().start(weak.clone());
+        self.slides_thread.as_ref().unwrap().start(weak.clone());
+        self.sched_thread.as_ref().unwrap().start(weak.clone());
         // main thread control flow loop
         self.log("pandora", "startup completed; entering into ipc listen loop! :3".to_string());
         self.cmd_ipc_thread.as_ref().unwrap().start(weak);
@@ -133,7 +270,16 @@ impl Pandora {
         match cmd {
             CommandType::Dc(dc) => self.handle_daemon_command(&dc),
             // CommandType::Ac(ac) => {}
-            CommandType::Tc(tc) => self.handle_thread_command(&tc),
+            CommandType::Tc(tc) => {
+                let ret = match tc {
+                    // whatever isn't a set/slideshow/schedule (those call render themselves) comes from the config
+                    RenderThreadCommand::Render(c) => self.render(c, ChangeCause::Config),
+                    _ => self.handle_thread_command(tc),
+                };
+                if let Err(e) = ret {
+                    self.log("pandora", format!("{e:?}"));
+                }
+            },
         };
     }
 
@@ -151,29 +297,59 @@ impl Pandora {
                 let _ = self.niri_ag_thread.as_ref().unwrap().queue.send(dc.clone());
             },
             DaemonCommand::Lock => self.lock(),
+            DaemonCommand::Slideshow(_) => {
+                let _ = self.slides_thread.as_ref().unwrap().queue.send(dc.clone());
+            },
+            DaemonCommand::ImageChanged(_) => {
+                let _ = self.niri_ag_thread.as_ref().unwrap().queue.send(dc.clone());
+                // so it watches the new image for changes on disk
+                self.configw_thread.as_ref().unwrap().send(dc.clone());
+            },
         };
     }
 
     fn lock(&self) {
         {
             match self.config.read() {
-                Ok(conf) => crate::threads::lockscreen::lock(self.logger.inbox.clone(), conf.clone()),
+                Ok(conf) => {
+                    for img in conf.lockscreen_images() {
+                        if let Err(e) = self.load_image(&img) {
+                            self.log("pandora", format!("could not load lockscreen image {img}: {e:?}"));
+                        }
+                    }
+                    crate::threads::lockscreen::lock(self.logger.inbox.clone(), conf.clone());
+                    self.emit(Event::Locked);
+                },
                 Err(_) => self.log("pandora", "locking screen failed: could not acquire config read-lock".to_string()),
             }
         }
         
     }
     
-    fn handle_thread_command(&self, tc: &RenderThreadCommand) {
+    fn handle_thread_command(&self, tc: &RenderThreadCommand) -> Result<(), DaemonError> {
         let output: String;
         let mut can_spawn = false;
         let mut join_after = false;
-        let mut image_to_preload: Option<String> = None;
         match tc.clone() {
             RenderThreadCommand::Render(c) => {
+                // images are loaded lazily by the render thread (and possibly not at all, if the disk cache has it)
+                // so just make sure it's something we can read before handing it off
+                match self.probe_image(&c.image) {
+                    Ok(_) => {},
+                    Err(DaemonError::MissingDecoder(d)) => {
+                        self.log("pandora", format!("not rendering on {}: {d} (rebuild with `--features {}`)", c.output, d.feature));
+                        let e = DaemonError::MissingDecoder(d);
+                        self.emit(Event::ImageFailed { image: c.image.clone(), message: e.to_string() });
+                        return Err(e);
+                    },
+                    Err(e) => {
+                        self.log("pandora", format!("not rendering {} on {}: {e:?}", c.image, c.output));
+                        self.emit(Event::ImageFailed { image: c.image.clone(), message: e.to_string() });
+                        return Err(e);
+                    },
+                }
                 output = c.output;
                 can_spawn = true;
-                image_to_preload = Some(c.image);
             }
             RenderThreadCommand::Stop(c) => {
                 output = c.output;
@@ -183,15 +359,21 @@ impl Pandora {
                 output = c.output;
             },
         };
-        if image_to_preload.is_some() {
-            self.handle_daemon_command(&DaemonCommand::LoadImage(LoadImageCommand { image:image_to_preload.unwrap() }));
-        }
         let ret = self.dispatch_thread_command(output.clone(), &tc, can_spawn);
+        if let (RenderThreadCommand::Render(c), Ok(_)) = (tc, &ret) {
+            match self.renders.write() {
+                Ok(mut renders) => _ = renders.insert(output.clone(), c.clone()),
+                Err(e) => self.log("pandora", format!("could not record render for {output}: {e:?}")),
+            }
+            // the niri agent needs the new image's height to keep scrolling in step
+            self.handle_daemon_command(&DaemonCommand::ImageChanged(c.clone()));
+        }
         if join_after && ret.is_ok() { // if a stop command error'd in dispatch, it either crashed or didn't exist; no need to clean up
             // if we full-steam ahead, we will get to .is_finished before the thread might be finished
             thread::sleep(Duration::from_millis(1)); // seems to be sufficient for letting the thread exit before we clean it up
             self.cleanup_thread(&output);
         }
+        ret
     }
 
     fn dispatch_thread_command(&self, output: String, c: &RenderThreadCommand, spawn: bool) -> Result<(), DaemonError> {
@@ -206,14 +388,14 @@ impl Pandora {
                     }
                     drop(read_threads); // drop read thread bc we're done reading & need to re-enter to clean up thread
                     self.cleanup_thread(&output);
-                    return Err(CommandError::new("could not send command to thread; cleaned it up"));
+                    return Err(CommandError::coded(ErrorCode::Internal, "could not send command to thread; cleaned it up"));
                 },
                 None => {
                     drop(read_threads); // release lock, result not found.
                     if spawn {
                        return self.spawn_thread(output, c);
                     } else {
-                        return Err(CommandError::new("invalid thread command: thread does not exist"));
+                        return Err(CommandError::coded(ErrorCode::NotFound, "invalid thread command: thread does not exist"));
                     }
                 },
             }
@@ -224,6 +406,11 @@ impl Pandora {
     fn spawn_thread(&self, output: String, c: &RenderThreadCommand) -> Result<(), DaemonError> {
         let thread = ThreadHandle::new(output.clone(), Arc::new(self.clone()));
         thread.sender.send(c.clone()).expect("could not send initial command to thread after spawning");
+        // a fresh thread (startup, a replugged output) goes back to wherever it was last scrolled by hand
+        if let RenderThreadCommand::Render(r) = c
+            && let Some(position) = self.output_override(&output).and_then(|o| o.scroll) {
+            _ = thread.sender.send(RenderThreadCommand::Scroll(ScrollCommand { output: r.output.clone(), position }));
+        }
         {
             let mut write_threads = self.threads.write()?;
             write_threads.insert(output, thread);
@@ -232,17 +419,39 @@ impl Pandora {
     }
 
     pub fn load_image(&self, path: &String) -> Result<(), DaemonError>  {
-        let img= ImageReader::open(path.clone())?.decode()?;
+        let loaded = self.decode_image(path);
+        if let Err(e) = &loaded {
+            self.emit(Event::ImageFailed { image: path.clone(), message: e.to_string() });
+        }
+        loaded
+    }
+
+    fn decode_image(&self, path: &String) -> Result<(), DaemonError>  {
+        if self.images.read()?.contains(path) {
+            self.verbose("pandora", format!("file {} already loaded", path.clone()));
+            return Ok(());
+        }
+        let (img, space) = match svg::is_svg(Path::new(path)) {
+            // at the size the document asks for; outputs get their own rasterization in fill_buffer
+            true => {
+                let tree = svg::parse(Path::new(path))?;
+                let (width, height) = svg::dimensions(&tree);
+                (svg::pixmap_into_image(&svg::rasterize(&tree, width, height)?).into(), ColorSpace::Srgb)
+            },
+            false => decoders::decode(Path::new(path), &self.color_spaces())?,
+        };
+        self.image_colors.write()?.insert(path.clone(), space);
         {
             let images_lock = self.images.write();
             match images_lock {
                 Ok(mut images_table) => {
-                    if images_table.contains_key(&path.clone()) {
+                    if images_table.contains(&path.clone()) {
                         self.verbose("pandora", format!("file {} already loaded", path.clone()));
                         return Ok(());
                     }
-                    images_table.insert(path.clone(), img.into_rgba8());
+                    let evicted = images_table.insert(path.clone(), img.into_rgba8());
                     self.log("pandora", format!("file {} loaded", path.clone()));
+                    self.log_evictions(&evicted);
                     return Ok(());
                 }
                 Err(e) => panic!("{e:?}"),
@@ -250,62 +459,917 @@ impl Pandora {
         }
     }
 
+    // the image an output should be showing right now: whatever its schedule says, wherever its slideshow is at,
+    // or just its configured file
+    pub fn resolve_image(&self, output: &OutputConfig) -> String {
+        // `pandora set` beats all of that, until a slideshow/schedule moves on or `pandora reset`
+        if let Some(image) = self.output_override(&output.name).and_then(|o| o.image) {
+            return image;
+        }
+        if let Some(entries) = self.schedule_entries(output)
+            && let Some(image) = self.scheduled_image(&entries) {
+            return image;
+        }
+        let path = Path::new(&output.image.path);
+        if is_kde_package(path) {
+            let size = self.output_size(&output.name);
+            match closest_variant(&kde_variants(path), size) {
+                Some(variant) => return variant.path.clone(),
+                None => self.log("pandora", format!("{} has no images in contents/images", output.image.path)),
+            }
+        }
+        let key = self.slideshow_key(&output.name);
+        if let Ok(mut slideshows) = self.slideshows.write()
+            && let Some(show) = slideshows.get_mut(&key)
+            && let Some(current) = show.current() {
+            return current;
+        }
+        output.image.path.clone()
+    }
+
+    pub fn resolve_mode(&self, output: &OutputConfig) -> RenderMode {
+        self.output_override(&output.name).and_then(|o| o.mode)
+            .or(output.mode)
+            .unwrap_or(RenderMode::Static)
+    }
+
+    fn output_override(&self, output: &str) -> Option<OutputOverride> {
+        self.state.read().ok()?.outputs.get(output).cloned()
+    }
+
+    // whether `pandora set` put something on an output, config or not
+    pub fn has_override(&self, output: &str) -> bool {
+        self.output_override(output).is_some_and(|o| o.image.is_some())
+    }
+
+    // changes the runtime overrides and writes them out if that changed anything
+    fn update_state(&self, change: impl FnOnce(&mut RuntimeState)) {
+        match self.state.write() {
+            Ok(mut state) => {
+                let before = state.clone();
+                change(&mut state);
+                if *state != before && let Err(e) = state.save(&get_state_file()) {
+                    self.log("pandora", format!("could not save runtime state to {}: {e}", get_state_file().display()));
+                }
+            },
+            Err(e) => self.log("pandora", format!("could not update runtime state: {e:?}")),
+        }
+    }
+
+    // where every slideshow is at, so a restart carries on from there
+    fn save_slideshows(&self) {
+        let saved: BTreeMap<String, SlideshowState> = match self.slideshows.read() {
+            Ok(slideshows) => slideshows.iter()
+                .filter(|(_, show)| show.paused || show.shown().is_some())
+                .map(|(key, show)| (key.clone(), SlideshowState { image: show.shown(), paused: show.paused }))
+                .collect(),
+            Err(e) => return self.log("pandora", format!("could not read slideshows: {e:?}")),
+        };
+        self.update_state(|state| state.slideshows = saved);
+    }
+
+    // forgets what set/scroll/next/prev/pause did to one output (or all of them) and redraws from the config.
+    // returns the outputs that got redrawn
+    fn reset(&self, output: Option<&String>) -> Result<Vec<String>, DaemonError> {
+        let key = output.map(|o| self.slideshow_key(o));
+        self.update_state(|state| match (output, &key) {
+            (Some(output), Some(key)) => {
+                state.outputs.remove(output);
+                state.slideshows.remove(key);
+            },
+            _ => *state = RuntimeState::default(),
+        });
+        for (name, show) in self.slideshows.write()?.iter_mut() {
+            if key.as_ref().is_none_or(|k| k == name) && show.paused {
+                show.toggle_pause();
+            }
+        }
+        let config = self.config()?;
+        // so it works out its next deadline with the unpaused ones in
+        let _ = self.slides_thread.as_ref().unwrap().queue.send(DaemonCommand::ReloadConfig(config.clone()));
+        let shown: Vec<String> = self.renders.read()?.keys().filter(|o| output.is_none_or(|t| t == *o)).cloned().collect();
+        let mut redrawn = Vec::new();
+        for name in shown {
+            match config.outputs.iter().find(|o| o.name == name) {
+                Some(output_config) => self.handle_cmd(&CommandType::Tc(RenderThreadCommand::Render(RenderCommand {
+                    output: name.clone(),
+                    image: self.resolve_image(output_config),
+                    mode: self.resolve_mode(output_config),
+                }))),
+                // nothing to go back to; it can keep what it has until it's unplugged
+                None => continue,
+            }
+            redrawn.push(name);
+        }
+        Ok(redrawn)
+    }
+
+    // the output's own schedule, or the one a GNOME timed background turns into
+    pub fn schedule_entries(&self, output: &OutputConfig) -> Option<Vec<ScheduleEntry>> {
+        if let Some(schedule) = &output.schedule {
+            return Some(schedule.entries.clone());
+        }
+        let path = Path::new(&output.image.path);
+        if !is_gnome_background(path) {
+            return None;
+        }
+        match gnome_schedule(path, self.output_size(&output.name)) {
+            Ok(entries) => Some(entries),
+            Err(e) => {
+                self.log("pandora", e);
+                None
+            },
+        }
+    }
+
+    pub fn scheduled_image(&self, entries: &[ScheduleEntry]) -> Option<String> {
+        let location = self.config.read().ok()?.location;
+        let (current, _) = current_entry(entries, location.as_ref(), &chrono::Local::now());
+        current.map(|e| e.image.clone())
+    }
+
+    pub fn set_output_size(&self, output: &str, width: u32, height: u32) {
+        match self.output_sizes.write() {
+            Ok(mut sizes) => _ = sizes.insert(output.to_string(), (width, height)),
+            Err(e) => self.log("pandora", format!("could not record size of {output}: {e:?}")),
+        }
+    }
+
+    fn output_size(&self, output: &str) -> Option<(u32, u32)> {
+        self.output_sizes.read().ok()?.get(output).copied()
+    }
+
+    // set once by the outputs thread, which binds the color manager before any output (so before anything renders)
+    pub fn set_color_spaces(&self, spaces: Vec<ColorSpace>) {
+        self.verbose("pandora", format!("compositor takes surfaces tagged as {spaces:?}"));
+        match self.color_spaces.write() {
+            Ok(mut current) => *current = spaces,
+            Err(e) => self.log("pandora", format!("could not record supported color spaces: {e:?}")),
+        }
+    }
+
+    pub fn color_spaces(&self) -> Vec<ColorSpace> {
+        self.color_spaces.read().map(|s| s.clone()).unwrap_or_default()
+    }
+
+    // what a render thread should tag the surface as. read from the file's headers if it hasn't been decoded yet;
+    // svgs and animations are always plain sRGB
+    pub fn image_color(&self, path: &String) -> ColorSpace {
+        if let Some(space) = self.image_colors.read().ok().and_then(|c| c.get(path).copied()) {
+            return space;
+        }
+        let file = Path::new(path);
+        let space = match svg::is_svg(file) || is_animated(file) {
+            true => ColorSpace::Srgb,
+            false => decoders::color_space(file, &self.color_spaces()).unwrap_or(ColorSpace::Srgb),
+        };
+        if let Ok(mut colors) = self.image_colors.write() {
+            colors.insert(path.clone(), space);
+        }
+        space
+    }
+
+    // crop/rotate/flip from an output's image node, done before any scaling
+    // grouped outputs also get their slice of the group's image cut out, which depends on the image's size
+    pub fn output_transform(&self, output: &str, image: &String) -> Transform {
+        let (mut transform, group) = match self.config.read() {
+            Ok(config) => match config.outputs.iter().find(|o| o.name == output) {
+                Some(o) => (o.image.transform(), config.group_of(output).cloned()),
+                None => return Transform::default(),
+            },
+            Err(_) => return Transform::default(),
+        };
+        if let Some(group) = group
+            && let Ok(layout) = self.output_layout.read()
+            && let Ok(dims) = self.transformed_dimensions(image, &transform) {
+            let members: Vec<(String, LogicalRect)> = group.outputs.iter()
+                .filter_map(|m| layout.get(m).map(|rect| (m.clone(), *rect)))
+                .collect();
+            transform.region = group_region(&members, output, group.bezel.unwrap_or(0), dims, group.mode.unwrap_or(RenderMode::Static));
+        }
+        transform
+    }
+
+    pub fn set_output_layout(&self, output: &str, rect: LogicalRect) {
+        let changed = match self.output_layout.write() {
+            Ok(mut layout) => layout.insert(output.to_string(), rect) != Some(rect),
+            Err(e) => {
+                self.log("pandora", format!("could not record layout of {output}: {e:?}"));
+                return;
+            },
+        };
+        if changed {
+            self.rerender_group(output);
+        }
+    }
+
+    pub fn remove_output_layout(&self, output: &str) {
+        let removed = self.output_layout.write().is_ok_and(|mut layout| layout.remove(output).is_some());
+        if removed {
+            self.rerender_group(output);
+        }
+    }
+
+    // an output in a group moved, (dis)appeared or resized, so the rest of the group's slices are off now
+    fn rerender_group(&self, output: &str) {
+        let members = match self.config.read() {
+            Ok(config) => match config.group_of(output) {
+                Some(group) => group.outputs.clone(),
+                None => return,
+            },
+            Err(_) => return,
+        };
+        let renders: Vec<RenderCommand> = match self.renders.read() {
+            Ok(renders) => renders.values().filter(|r| r.output != output && members.contains(&r.output)).cloned().collect(),
+            Err(_) => return,
+        };
+        for render in renders {
+            self.verbose("pandora", format!("re-cutting {} for {} after a layout change", render.image, render.output));
+            self.handle_cmd(&CommandType::Tc(RenderThreadCommand::Render(render)));
+        }
+    }
+
+    // an output group's members share one slideshow, kept under the group's name
+    fn slideshow_key(&self, output: &str) -> String {
+        self.config.read().ok()
+            .and_then(|c| c.group_of(output).map(|g| g.name.clone()))
+            .unwrap_or(output.to_string())
+    }
+
+    pub fn output_layers(&self, output: &str) -> Vec<LayerConfig> {
+        self.config.read().ok()
+            .and_then(|c| c.outputs.iter().find(|o| o.name == output).map(|o| o.layers.clone()))
+            .unwrap_or_default()
+    }
+
+    // what an output's wallpaper gets run through after scaling; nothing if it isn't configured
+    pub fn output_effects(&self, output: &str) -> Effects {
+        self.config.read().ok()
+            .and_then(|c| c.outputs.iter().find(|o| o.name == output).map(|o| o.effects.clone()))
+            .unwrap_or_default()
+    }
+
+    // how long the slideshow thread can sleep for
+    pub fn next_slide_in(&self) -> Option<Duration> {
+        let slideshows = self.slideshows.read().ok()?;
+        slideshows.values()
+            .filter(|s| !s.paused)
+            .map(|s| s.next_at.saturating_duration_since(Instant::now()))
+            .min()
+    }
+
+    pub fn advance_due_slideshows(&self) {
+        let mut slides = Vec::new();
+        match self.slideshows.write() {
+            Ok(mut slideshows) => {
+                let now = Instant::now();
+                for (output, show) in slideshows.iter_mut() {
+                    if !show.paused && show.next_at <= now
+                        && let Some(image) = self.next_fitting(output, show, Slideshow::advance) {
+                        slides.push((output.clone(), image, show.peek()));
+                    }
+                }
+            },
+            Err(e) => self.log("pandora", format!("could not advance slideshows: {e:?}")),
+        }
+        for (output, image, upcoming) in slides {
+            self.show_image(&output, image, ChangeCause::Slideshow);
+            if let Some(next) = upcoming {
+                self.preload_image(next);
+            }
+        }
+        self.save_slideshows();
+    }
+
+    pub fn slideshow_command(&self, c: &SlideshowCommand) {
+        let mut slides = Vec::new();
+        // naming any member of a group steers the group's slideshow
+        let target = c.output.as_ref().map(|o| self.slideshow_key(o));
+        match self.slideshows.write() {
+            Ok(mut slideshows) => {
+                for (output, show) in slideshows.iter_mut() {
+                    if target.as_ref().is_some_and(|o| o != output) {
+                        continue;
+                    }
+                    let image = match c.action {
+                        SlideshowAction::Next => self.next_fitting(output, show, Slideshow::advance),
+                        SlideshowAction::Prev => self.next_fitting(output, show, Slideshow::back),
+                        SlideshowAction::Pause => {
+                            let paused = show.toggle_pause();
+                            self.log("pandora", format!("slideshow on {output} {}", if paused { "paused" } else { "resumed" }));
+                            None
+                        },
+                    };
+                    if let Some(image) = image {
+                        slides.push((output.clone(), image, show.peek()));
+                    }
+                }
+            },
+            Err(e) => self.log("pandora", format!("could not update slideshows: {e:?}")),
+        }
+        for (output, image, upcoming) in slides {
+            self.show_image(&output, image, ChangeCause::Slideshow);
+            if let Some(next) = upcoming {
+                self.preload_image(next);
+            }
+        }
+        self.save_slideshows();
+    }
+
+    // steps a slideshow along (or back) until it lands on an image its output can show, giving up after a lap.
+    // a directory is bound to have the odd image too small for a scroll mode, and that shouldn't stop the show
+    fn next_fitting(&self, output: &String, show: &mut Slideshow, step: fn(&mut Slideshow) -> Option<String>) -> Option<String> {
+        for _ in 0..list_images(&show.source).len().max(1) {
+            let image = step(show)?;
+            match self.fits(output, &image) {
+                Ok(_) => return Some(image),
+                Err(e) => self.log("pandora", format!("skipping {image} on {output}: {e}")),
+            }
+        }
+        None
+    }
+
+    // check_fits for whatever an output (or every member of a group) is showing now; outputs that aren't plugged in
+    // don't count
+    fn fits(&self, output: &String, image: &String) -> Result<(), DaemonError> {
+        let members = self.config.read()?.groups.iter()
+            .find(|g| g.name == *output)
+            .map_or(vec![output.clone()], |g| g.outputs.clone());
+        for member in members {
+            let mode = match self.renders.read()?.get(&member) {
+                Some(r) => r.mode,
+                None => continue,
+            };
+            self.check_fits(&member, image, mode)?;
+        }
+        Ok(())
+    }
+
+    // repoints an output's render thread at another image, keeping its mode.
+    // false if the image can't cover the output (or some member of the group), in which case nothing changes
+    pub fn show_image(&self, output: &String, image: String, cause: ChangeCause) -> bool {
+        if let Err(e) = self.fits(output, &image) {
+            self.log("pandora", format!("not showing {image} on {output}: {e}"));
+            return false;
+        }
+        // a group's slideshow moves all of it along
+        let members = self.config.read().ok()
+            .and_then(|c| c.groups.iter().find(|g| g.name == *output).map(|g| g.outputs.clone()));
+        if let Some(members) = members {
+            for member in members {
+                self.show_image(&member, image.clone(), cause);
+            }
+            return true;
+        }
+        // don't spin up render threads for outputs that aren't plugged in
+        let mode = match self.renders.read() {
+            Ok(renders) => match renders.get(output) {
+                Some(r) => r.mode,
+                None => return true,
+            },
+            Err(_) => RenderMode::Static,
+        };
+        // a slideshow or schedule moving on takes over from whatever `pandora set` put here
+        self.update_state(|state| _ = state.outputs.remove(output));
+        self.verbose("pandora", format!("switching {output} to {image}"));
+        let render = RenderCommand { output: output.clone(), image, mode };
+        if let Err(e) = self.render(&render, cause) {
+            self.log("pandora", format!("{e:?}"));
+        }
+        true
+    }
+
+    // a render that goes in the output's history if it actually changes anything
+    fn render(&self, c: &RenderCommand, cause: ChangeCause) -> Result<(), DaemonError> {
+        self.handle_thread_command(&RenderThreadCommand::Render(c.clone()))?;
+        let at = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+        let entry = HistoryEntry { image: c.image.clone(), mode: c.mode, at, cause };
+        let (old_target, new_target) = {
+            let mut history = self.history.write()?;
+            let history = history.entry(c.output.clone()).or_default();
+            let old_target = history.previous().map(|e| e.image.clone());
+            history.push(entry);
+            (old_target, history.previous().map(|e| e.image.clone()))
+        };
+        self.retarget_undo(old_target, new_target);
+        Ok(())
+    }
+
+    // keeps whatever undo would go back to decoded, so it's instant rather than a trip through the decoder:
+    // pinned like the lockscreen's images, one per output
+    fn retarget_undo(&self, old: Option<String>, new: Option<String>) {
+        if old == new {
+            return;
+        }
+        match self.images.write() {
+            Ok(mut images) => {
+                if let Some(new) = &new {
+                    images.pin(new);
+                }
+                if let Some(old) = &old {
+                    let evicted = images.unpin(old);
+                    self.log_evictions(&evicted);
+                }
+            },
+            Err(e) => return self.log("pandora", format!("could not pin undo image: {e:?}")),
+        }
+        if let Some(new) = new {
+            self.preload_image(new);
+        }
+    }
+
+    // back to what an output showed before its last change. returns what that was
+    fn undo(&self, output: &String) -> Result<HistoryEntry, DaemonError> {
+        // the output may have changed size since, so don't throw away the newest entry for something that can't be shown
+        let target = self.history.read()?.get(output).and_then(|h| h.previous().cloned());
+        if let Some(target) = &target {
+            self.check_fits(output, &target.image, target.mode)?;
+        }
+        let (entry, old_target, new_target) = {
+            let mut history = self.history.write()?;
+            let history = match history.get_mut(output) {
+                Some(history) => history,
+                None => return Err(CommandError::coded(ErrorCode::NotFound, &format!("nothing has been shown on {output}"))),
+            };
+            let old_target = history.previous().map(|e| e.image.clone());
+            let entry = match history.undo() {
+                Some(entry) => entry,
+                None => return Err(CommandError::coded(ErrorCode::NotFound, &format!("nothing to undo on {output}"))),
+            };
+            (entry, old_target, history.previous().map(|e| e.image.clone()))
+        };
+        self.retarget_undo(old_target, new_target);
+        self.handle_thread_command(&RenderThreadCommand::Render(RenderCommand {
+            output: output.clone(),
+            image: entry.image.clone(),
+            mode: entry.mode,
+        }))?;
+        // sticks like a set would
+        self.update_state(|state| _ = state.outputs.insert(output.clone(), OutputOverride {
+            image: Some(entry.image.clone()),
+            mode: Some(entry.mode),
+            scroll: None,
+        }));
+        Ok(entry)
+    }
+
+    // newest first
+    fn history(&self, output: &String) -> Result<Vec<HistoryEntry>, DaemonError> {
+        match self.history.read()?.get(output) {
+            Some(history) => Ok(history.newest_first()),
+            None => Err(CommandError::coded(ErrorCode::NotFound, &format!("nothing has been shown on {output}"))),
+        }
+    }
+
+    // decode an image in the background so switching to it later is quick
+    pub fn preload_image(&self, image: String) {
+        let pandora = self.clone();
+        thread::spawn(move || {
+            if let Err(e) = pandora.load_image(&image) {
+                pandora.log("pandora", format!("could not preload {image}: {e:?}"));
+            }
+        });
+    }
+
+    // the file behind `path` changed: forget everything we derived from it and redraw whoever's showing it.
+    // the disk cache keys on mtime/size, so it needs no help here
+    pub fn reload_image(&self, path: &String) -> Result<(), DaemonError> {
+        let was_loaded = self.images.write()?.remove(path);
+        self.image_colors.write()?.remove(path);
+        let invalidated = self.buffers.write()?.invalidate(path);
+        self.verbose("pandora", format!("{path} changed on disk; invalidated {invalidated} buffer(s)"));
+        if was_loaded {
+            self.load_image(path)?;
+        }
+
+        // layers are part of the output's render, so those get redrawn whole too
+        let layered: Vec<String> = self.config.read()?.outputs.iter()
+            .filter(|o| o.layers.iter().any(|l| l.image == *path))
+            .map(|o| o.name.clone())
+            .collect();
+        let renders: Vec<RenderCommand> = self.renders.read()?.values()
+            .filter(|r| r.image == *path || layered.contains(&r.output))
+            .cloned()
+            .collect();
+        for render in renders {
+            // it may have been overwritten with something smaller; then the old render stays up
+            if let Err(e) = self.check_fits(&render.output, &render.image, render.mode) {
+                self.log("pandora", format!("not re-rendering {} on {}: {e}", render.image, render.output));
+                continue;
+            }
+            self.log("pandora", format!("re-rendering {} on {}", render.image, render.output));
+            self.handle_cmd(&CommandType::Tc(RenderThreadCommand::Render(render)));
+        }
+        Ok(())
+    }
+
     pub fn get_image_dimensions(&self, img: String) -> Result<(u32, u32), ()> {
         {
             let images_lock = self.images.read();
             match images_lock {
                 Ok(images_table) => {
-                    if images_table.contains_key(&img) {
-                        let image = images_table.get(&img).unwrap();
-                        return Ok((image.width(), image.height()));
-                    } else {
-                        return Err(());
-                    }
+                    return images_table.dimensions(&img).ok_or(());
                 },
                 Err(e) => panic!("{e:?}"),
             };
         }
     }
 
+    // dimensions of an image, without decoding it if it isn't loaded yet
+    pub fn probe_image(&self, path: &String) -> Result<(u32, u32), DaemonError> {
+        if let Ok(dims) = self.get_image_dimensions(path.clone()) {
+            return Ok(dims);
+        }
+        if svg::is_svg(Path::new(path)) {
+            return Ok(svg::dimensions(&svg::parse(Path::new(path))?));
+        }
+        decoders::dimensions(Path::new(path))
+    }
+
+    // every image some render thread is showing right now
+    pub fn shown_images(&self) -> Vec<String> {
+        match self.renders.read() {
+            Ok(renders) => renders.values().map(|r| r.image.clone()).collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    // what the scaling maths should work from: the image's size once it's been cropped/rotated
+    pub fn transformed_dimensions(&self, path: &String, transform: &Transform) -> Result<(u32, u32), DaemonError> {
+        let (width, height) = self.probe_image(path)?;
+        Ok(transform.dimensions(width, height))
+    }
+
+    // whether `image` still covers the whole output once scaled for `mode`. the render thread can't show one that
+    // doesn't, so requests get checked up front; an output we don't know the size of yet gets the benefit of the doubt
+    fn check_fits(&self, output: &str, image: &String, mode: RenderMode) -> Result<(), DaemonError> {
+        let (output_width, output_height) = match self.output_size(output) {
+            Some(size) => size,
+            None => return Ok(()),
+        };
+        let (width, height) = self.transformed_dimensions(image, &self.output_transform(output, image))?;
+        let (scale_width, scale_height) = mode_scale_to(mode, output_width, output_height);
+        let (width, height) = get_new_image_dimensions(width, height, scale_width, scale_height);
+        if width < output_width || height < output_height {
+            let mode = format!("{mode:?}").to_lowercase();
+            return Err(CommandError::new(&format!("{image} only scales to {width}x{height} in {mode} mode, which doesn't cover {output} ({output_width}x{output_height})")));
+        }
+        Ok(())
+    }
+
     // if scale_to is provided, uses the provided width/height dimensions of the output to scale image appropriately
     // if only one dimension is provided, scales to that one and keeps aspect ratio.
-    pub fn read_img_to_file(&self, img: &String, f: &File, scale_to: Option<(Option<u32>, Option<u32>)>) -> Result<(u32, u32), DaemonError> {
-        let mut image = None;
+    // the transform happens before scaling, effects after (so they're in output pixels)
+    pub fn read_img_to_file(&self, img: &str, f: &File, scale_to: Option<(Option<u32>, Option<u32>)>, transform: &Transform, effects: &Effects) -> Result<(u32, u32), DaemonError> {
+        // clone the Arc out so the cache isn't locked while we scale
+        let image = self.images.write()?.get(img);
         {
-            let images = self.images.read()?;
-            if images.contains_key(img) {
-                image = Some(images.get(img).unwrap());
-            }
             if image.is_none() {
                 return Err(CommandError::new("invalid image (not loaded)"));
             }
 
-            let image = image.unwrap();
+            let image = image.as_deref().unwrap();
+            let transformed;
+            let image = match transform.is_identity() {
+                true => image,
+                false => {
+                    transformed = transform.apply(image);
+                    &transformed
+                }
+            };
             match scale_to {
                 Some((maybe_width, maybe_height)) => {
                     let (new_width, new_height) = get_new_image_dimensions(image.width(), image.height(), maybe_width, maybe_height);
-                    ::pandora::pithos::misc::img_into_buffer(
-                        &image::imageops::resize(
+                    let scaled = image::imageops::resize(
                         image,
                         new_width as u32,
                         new_height as u32,
-                        FilterType::Lanczos3,
-                        ),
-                        &f
+                        SCALE_FILTER,
                     );
+                    ::pandora::pithos::misc::img_into_buffer(&effects.apply(scaled), f);
                     return Ok((new_width, new_height));
                 },
                 None => {
-                    ::pandora::pithos::misc::img_into_buffer(image, &f);
+                    match effects.is_empty() {
+                        true => ::pandora::pithos::misc::img_into_buffer(image, f),
+                        false => ::pandora::pithos::misc::img_into_buffer(&effects.apply(image.clone()), f),
+                    }
                     return Ok((image.width(), image.height()));
                 }
             };
         }
     }
 
+    // hands out a buffer of the (scaled) image, filling it first if no other thread is using it yet
+    pub fn acquire_buffer(&self, img: &String, scale_to: Option<(Option<u32>, Option<u32>)>, format: Format, transform: &Transform, effects: &Effects) -> Result<SharedBuffer, DaemonError> {
+        if format != Format::Argb8888 {
+            return Err(CommandError::new("unsupported buffer format"));
+        }
+        let (orig_width, orig_height) = self.transformed_dimensions(img, transform)?;
+        let (width, height) = match scale_to {
+            Some((maybe_width, maybe_height)) => get_new_image_dimensions(orig_width, orig_height, maybe_width, maybe_height),
+            None => (orig_width, orig_height),
+        };
+        let key = BufferKey { image: img.clone(), width, height, format, transform: *transform, effects: effects.cache_id() };
+
+        if let Some(buf) = self.buffers.write()?.acquire(&key)? {
+            self.verbose("pandora", format!("sharing existing {width} x {height} buffer for {img}"));
+            return Ok(buf);
+        }
+
+        // scaling can take a bit, so don't hold the cache lock over it
+        if is_animated(Path::new(img)) {
+            let (file, delays) = self.fill_animation(img, &key, transform, effects)?;
+            return Ok(self.buffers.write()?.insert(key, file, delays)?);
+        }
+        let file = self.fill_buffer(img, scale_to, &key, transform, effects)?;
+        return Ok(self.buffers.write()?.insert(key, file, Vec::new())?);
+    }
+
+    // every frame scaled once and written back to back. frames are scaled as they're decoded so the full-size ones
+    // never all sit in memory at once; the disk cache is skipped since it can't hold the frame delays
+    fn fill_animation(&self, img: &String, key: &BufferKey, transform: &Transform, effects: &Effects) -> Result<(File, Vec<Duration>), DaemonError> {
+        let file = tempfile::tempfile()?;
+        let mut delays = Vec::new();
+        for frame in animation_frames(Path::new(img))? {
+            let frame = frame?;
+            delays.push(frame_delay(&frame));
+            let buffer = match transform.is_identity() {
+                true => frame.into_buffer(),
+                false => transform.apply(frame.buffer()),
+            };
+            let scaled = match buffer.dimensions() == (key.width, key.height) {
+                true => buffer,
+                false => image::imageops::resize(&buffer, key.width, key.height, SCALE_FILTER),
+            };
+            ::pandora::pithos::misc::img_into_buffer(&effects.apply(scaled), &file);
+        }
+        self.verbose("pandora", format!("scaled {} frames of {img} to {} x {}", delays.len(), key.width, key.height));
+        Ok((file, delays))
+    }
+
+    // pulls the scaled image out of the disk cache if we can, otherwise decodes + scales it (and caches that)
+    fn fill_buffer(&self, img: &String, scale_to: Option<(Option<u32>, Option<u32>)>, key: &BufferKey, transform: &Transform, effects: &Effects) -> Result<File, DaemonError> {
+        let disk_cache = self.disk_cache.read()?.clone();
+        let cache_name = match disk_cache.enabled() {
+            true => DiskCache::key(Path::new(img), key.width, key.height, &format!("{SCALE_FILTER:?}-{:?}{}{}", self.image_color(img), transform.cache_id(), key.effects)).ok(),
+            false => None,
+        };
+        if let Some(name) = &cache_name && let Some(file) = disk_cache.get(name, key.size_bytes()) {
+            self.verbose("pandora", format!("disk cache hit for {img} at {} x {}", key.width, key.height));
+            return Ok(file);
+        }
+
+        if svg::is_svg(Path::new(img)) {
+            return self.rasterize_into_file(img, key, transform, effects, &disk_cache, cache_name);
+        }
+
+        // pinned so nothing evicts it between decode and scale
+        self.images.write()?.pin(img);
+        let file = self.scale_into_file(img, scale_to, transform, effects, &disk_cache, cache_name);
+        let mut images = self.images.write()?;
+        let evicted = images.unpin(img);
+        self.log_evictions(&evicted);
+        // the scaled copy is what's on screen now; the original only sticks around if something else pinned it
+        if images.drop_original(img) {
+            self.verbose("pandora", format!("dropped original of {img} now that it's scaled"));
+        }
+        file
+    }
+
+    fn scale_into_file(&self, img: &String, scale_to: Option<(Option<u32>, Option<u32>)>, transform: &Transform, effects: &Effects, disk_cache: &DiskCache, cache_name: Option<String>) -> Result<File, DaemonError> {
+        self.load_image(img)?;
+        if let Some(name) = &cache_name {
+            match disk_cache.put(name, |f| self.read_img_to_file(img, f, scale_to, transform, effects).map(|_| ())) {
+                Ok(file) => return Ok(file),
+                Err(e) => self.log("pandora", format!("could not write {img} to disk cache: {e:?}")),
+            }
+        }
+        let file = tempfile::tempfile()?;
+        self.read_img_to_file(img, &file, scale_to, transform, effects)?;
+        Ok(file)
+    }
+
+    // svgs skip the decoded-image cache entirely and get drawn straight at the buffer's size
+    fn rasterize_into_file(&self, img: &str, key: &BufferKey, transform: &Transform, effects: &Effects, disk_cache: &DiskCache, cache_name: Option<String>) -> Result<File, DaemonError> {
+        let tree = svg::parse(Path::new(img))?;
+        let pixmap = match transform.is_identity() {
+            true => svg::rasterize(&tree, key.width, key.height)?,
+            // the whole document at whatever size leaves the cropped part at the buffer's size
+            false => {
+                let (width, height) = svg::dimensions(&tree);
+                let factor = key.width as f64 / transform.dimensions(width, height).0 as f64;
+                let scale = |v: u32| ((v as f64 * factor).round() as u32).max(1);
+                svg::rasterize(&tree, scale(width), scale(height))?
+            }
+        };
+        self.verbose("pandora", format!("rasterized {img} at {} x {}", pixmap.width(), pixmap.height()));
+        // effects and transforms need it as an image; without either the pixmap can go out as-is
+        let write = |f: &File| -> Result<(), DaemonError> {
+            if transform.is_identity() && effects.is_empty() {
+                return svg::pixmap_into_buffer(&pixmap, f);
+            }
+            let (width, _) = svg::dimensions(&tree);
+            let mut img = transform.scaled(pixmap.width() as f64 / width as f64).apply(&svg::pixmap_into_image(&pixmap));
+            if img.dimensions() != (key.width, key.height) { // rounding
+                img = image::imageops::resize(&img, key.width, key.height, SCALE_FILTER);
+            }
+            ::pandora::pithos::misc::img_into_buffer(&effects.apply(img), f);
+            Ok(())
+        };
+        if let Some(name) = &cache_name {
+            match disk_cache.put(name, write) {
+                Ok(file) => return Ok(file),
+                Err(e) => self.log("pandora", format!("could not write {img} to disk cache: {e:?}")),
+            }
+        }
+        let file = tempfile::tempfile()?;
+        write(&file)?;
+        Ok(file)
+    }
+
+    pub fn cache_stats(&self) -> Result<CacheStats, DaemonError> {
+        let disk_cache = self.disk_cache.read()?.clone();
+        return Ok(CacheStats {
+            images: self.images.read()?.stats(),
+            buffers: self.buffers.read()?.stats(),
+            disk: disk_cache.usage()?,
+            disk_limit_bytes: disk_cache.max_bytes(),
+        });
+    }
+
+    // done here rather than by the cli so it's the daemon's cache dir that gets emptied. buffers already mapped stay
+    // valid; the write lock just keeps a render from filling the cache back in halfway through
+    pub fn clear_disk_cache(&self) -> Result<DiskCacheUsage, DaemonError> {
+        let usage = self.disk_cache.write()?.clear()?;
+        self.log("pandora", format!("cleared {} cached images from disk", usage.files));
+        return Ok(usage);
+    }
+
+    // the daemon's own user, plus whoever the config trusts
+    pub fn ipc_allowed(&self, uid: u32) -> bool {
+        uid == unsafe { libc::getuid() } || self.config.read().is_ok_and(|c| c.ipc.allow_uid.contains(&uid))
+    }
+
+    // never blocks: see pithos::events
+    pub fn emit(&self, event: Event) {
+        match self.subscribers.write() {
+            Ok(mut subscribers) => {
+                if subscribers.is_empty() {
+                    return;
+                }
+                let slow = subscribers.send(&event);
+                if slow > 0 {
+                    self.log("ipc", format!("cut off {slow} event subscriber(s) that stopped reading"));
+                }
+            },
+            Err(e) => self.log("pandora", format!("could not send {event:?} to subscribers: {e:?}")),
+        }
+    }
+
+    // one subscriber's worth of events, written out until it hangs up (or falls too far behind)
+    fn stream_events(&self, socket: &UnixStream) {
+        let events = match self.subscribers.write() {
+            Ok(mut subscribers) => subscribers.subscribe(),
+            Err(e) => {
+                _ = write_response_to_client_socket(&DaemonError::from(e).to_response("could not subscribe"), socket);
+                return;
+            },
+        };
+        if write_response_to_client_socket(&Response::ok("streaming events"), socket).is_err() {
+            return;
+        }
+        while let Ok(event) = events.recv() {
+            if write_frame(socket, &event).is_err() {
+                break;
+            }
+        }
+        self.verbose("ipc", "event subscriber went away".to_string());
+    }
+
+    // None once the thread's torn down
+    pub fn set_render_status(&self, output: &str, status: Option<RenderStatus>) {
+        match self.render_status.write() {
+            Ok(mut statuses) => match status {
+                Some(status) => _ = statuses.insert(output.to_string(), status),
+                None => _ = statuses.remove(output),
+            },
+            Err(e) => self.log("pandora", format!("could not record render status of {output}: {e:?}")),
+        }
+    }
+
+    // json, for `pandora status` and friends
+    pub fn query(&self, query: Query) -> Result<serde_json::Value, DaemonError> {
+        let json = match query {
+            Query::Status => serde_json::to_value(Status {
+                outputs: self.outputs_status()?,
+                // the rest is still worth having if niri's socket is having a moment
+                workspaces: self.workspaces_status().unwrap_or_else(|e| {
+                    self.log("pandora", format!("could not get workspaces from niri: {e}"));
+                    Vec::new()
+                }),
+                threads: self.threads_status()?,
+                images: self.images_status()?,
+                config: serde_json::to_value(&*self.config.read()?).expect("could not serialize config"),
+            }),
+            Query::Outputs => serde_json::to_value(self.outputs_status()?),
+            Query::Workspaces => serde_json::to_value(self.workspaces_status()?),
+            Query::Threads => serde_json::to_value(self.threads_status()?),
+            Query::Images => serde_json::to_value(self.images_status()?),
+            Query::Config => serde_json::to_value(&*self.config.read()?),
+        };
+        Ok(json.expect("could not serialize query response"))
+    }
+
+    // anything we've seen a mode for, been told to draw on, or have config for
+    fn outputs_status(&self) -> Result<Vec<OutputStatus>, DaemonError> {
+        let config = self.config.read()?;
+        let sizes = self.output_sizes.read()?;
+        let layout = self.output_layout.read()?;
+        let renders = self.renders.read()?;
+        let slideshows = self.slideshows.read()?;
+        let mut names: Vec<&String> = sizes.keys().chain(renders.keys()).chain(config.outputs.iter().map(|o| &o.name)).collect();
+        names.sort();
+        names.dedup();
+        let now = Instant::now();
+        Ok(names.into_iter().map(|name| {
+            let group = config.group_of(name).map(|g| g.name.clone());
+            let slideshow = slideshows.get(group.as_ref().unwrap_or(name)).map(|show| SlideshowStatus {
+                paused: show.paused,
+                next_in_secs: if show.paused { 0 } else { show.next_at.saturating_duration_since(now).as_secs() },
+            });
+            let render = renders.get(name);
+            OutputStatus {
+                name: name.clone(),
+                size: sizes.get(name).copied(),
+                layout: layout.get(name).copied(),
+                group,
+                image: render.map(|r| r.image.clone()),
+                mode: render.map(|r| r.mode),
+                slideshow,
+            }
+        }).collect())
+    }
+
+    fn workspaces_status(&self) -> Result<Vec<WorkspaceStatus>, DaemonError> {
+        let config = self.config.read()?;
+        let mut workspaces = crate::threads::niri::workspaces()?;
+        workspaces.sort_by(|a, b| a.output.cmp(&b.output).then(a.idx.cmp(&b.idx)));
+        Ok(workspaces.into_iter().map(|w| {
+            let image = config.outputs.iter()
+                .filter(|o| w.output.as_ref() == Some(&o.name))
+                .flat_map(|o| o.workspaces.iter().flatten())
+                .find(|wsc| w.name.as_ref() == Some(&wsc.name))
+                .map(|wsc| wsc.image.clone());
+            WorkspaceStatus {
+                id: w.id,
+                idx: w.idx,
+                name: w.name,
+                output: w.output,
+                active: w.is_active,
+                focused: w.is_focused,
+                image,
+            }
+        }).collect())
+    }
+
+    fn threads_status(&self) -> Result<Vec<ThreadStatus>, DaemonError> {
+        let threads = self.threads.read()?;
+        let statuses = self.render_status.read()?;
+        let mut status: Vec<ThreadStatus> = threads.iter().map(|(output, thread)| ThreadStatus {
+            output: output.clone(),
+            alive: !thread.thread.is_finished(),
+            render: statuses.get(output).cloned(),
+        }).collect();
+        status.sort_by(|a, b| a.output.cmp(&b.output));
+        Ok(status)
+    }
+
+    fn images_status(&self) -> Result<ImagesStatus, DaemonError> {
+        Ok(ImagesStatus {
+            decoded: self.images.read()?.entries(),
+            buffers: self.buffers.read()?.entries(),
+        })
+    }
+
+    pub fn release_buffer(&self, buf: &SharedBuffer) {
+        match self.buffers.write() {
+            Ok(mut buffers) => {
+                if buffers.release(buf) {
+                    self.verbose("pandora", format!("dropped {} x {} buffer for {} ({} still cached)",
+                        buf.key.width, buf.key.height, buf.key.image, buffers.len()));
+                }
+            },
+            Err(e) => self.log("pandora", format!("could not release buffer: {e:?}")),
+        }
+    }
+
     fn cleanup_thread(&self, output: &String) {
         {
             let mut write_threads = self.threads.write().expect("could not acquire read lock for dispatching command");
+            if let Ok(mut renders) = self.renders.write() {
+                renders.remove(output);
+            }
             match write_threads.remove(output) {
                 Some(thread) => {
                     write_threads.shrink_to_fit();
@@ -324,8 +1388,148 @@ impl Pandora {
     }
 
     pub fn process_ipc(&self, socket: &UnixStream) {
-        let cmd = ::pandora::pithos::sockets::read_command_from_client_socket(&socket.try_clone().expect("couldn't clone socket"));
-        self.handle_cmd(&cmd);
-        write_response_to_client_socket("command dispatched", socket).expect("failed to write response to inbound ipc");
+        if let Err(e) = accept_client_hello(socket) {
+            self.verbose("ipc", format!("dropped a client that didn't shake hands: {e}"));
+            return;
+        }
+        let request = match read_command_from_client_socket(socket) {
+            Ok(request) => request,
+            Err(e) => {
+                _ = write_response_to_client_socket(&Response::err(ErrorCode::BadRequest, format!("could not read command: {e}")), socket);
+                return;
+            },
+        };
+        if let Request::EventStream = request {
+            return self.stream_events(socket);
+        }
+        if let Request::Stop = request {
+            // answer first, there's no daemon left to do it afterwards
+            _ = write_response_to_client_socket(&Response::ok("daemon stopped"), socket);
+            self.handle_cmd(&CommandType::Dc(DaemonCommand::Stop));
+        }
+        let response = self.answer_ipc(&request);
+        if let Err(e) = write_response_to_client_socket(&response, socket) {
+            self.verbose("ipc", format!("could not answer client (hung up?): {e}"));
+        }
     }
-}
\ No newline at end of file
+
+    // what the cli gets told. anything that can be refused is run here so the reason makes it back to whoever asked;
+    // the rest is just handed off
+    fn answer_ipc(&self, request: &Request) -> Response {
+        match request {
+            Request::CacheStats => match self.cache_stats() {
+                Ok(stats) => Response::Ok(serde_json::to_value(&stats).expect("could not serialize cache stats")),
+                Err(e) => e.to_response("could not collect cache stats"),
+            },
+            Request::CacheClear => match self.clear_disk_cache() {
+                Ok(usage) => Response::Ok(serde_json::to_value(&usage).expect("could not serialize cache usage")),
+                Err(e) => e.to_response("could not clear the disk cache"),
+            },
+            Request::Query(q) => match self.query(*q) {
+                Ok(answer) => Response::Ok(answer),
+                Err(e) => e.to_response(&format!("could not answer {q:?} query")),
+            },
+            Request::LoadImage(c) => match self.load_image(&c.image) {
+                Ok(_) => Response::ok(format!("loaded {}", c.image)),
+                Err(e) => e.to_response(&format!("could not load {}", c.image)),
+            },
+            // parsed here rather than by the client, so it's the daemon's idea of the config file that counts
+            Request::Reload => match config::reload_config() {
+                Ok(new_config) => {
+                    self.handle_cmd(&CommandType::Dc(DaemonCommand::ReloadConfig(new_config)));
+                    Response::ok("config reloaded")
+                },
+                Err(e) => Response::err(ErrorCode::Config, format!("{e:?}")),
+            },
+            Request::Render(c) => {
+                // a typo'd output would otherwise get a render thread that never finds anything to draw on
+                let unknown = self.output_sizes.read().is_ok_and(|known| !known.is_empty() && !known.contains_key(&c.output));
+                if unknown {
+                    return Response::err(ErrorCode::NotFound, format!("no output named {}", c.output));
+                }
+                if let Err(e) = self.check_fits(&c.output, &c.image, c.mode) {
+                    return e.to_response(&format!("could not show {} on {}", c.image, c.output));
+                }
+                match self.render(c, ChangeCause::Set) {
+                    Ok(_) => {
+                        self.update_state(|state| _ = state.outputs.insert(c.output.clone(), OutputOverride {
+                            image: Some(c.image.clone()),
+                            mode: Some(c.mode),
+                            scroll: None,
+                        }));
+                        Response::ok(format!("showing {} on {}", c.image, c.output))
+                    },
+                    Err(e) => e.to_response(&format!("could not show {} on {}", c.image, c.output)),
+                }
+            },
+            Request::Scroll(c) => {
+                let mode = match self.renders.read() {
+                    Ok(renders) => renders.get(&c.output).map(|r| r.mode),
+                    Err(e) => return DaemonError::from(e).to_response("could not look up renders"),
+                };
+                match mode {
+                    None => return Response::err(ErrorCode::NotFound, format!("nothing is being shown on {}", c.output)),
+                    Some(RenderMode::Static) => return Response::err(ErrorCode::InvalidArgument, format!("{} isn't scrolling", c.output)),
+                    Some(_) => (),
+                }
+                match self.handle_thread_command(&RenderThreadCommand::Scroll(c.clone())) {
+                    Ok(_) => {
+                        self.update_state(|state| state.outputs.entry(c.output.clone()).or_default().scroll = Some(c.position));
+                        Response::ok(format!("scrolled {} to {}", c.output, c.position))
+                    },
+                    Err(e) => e.to_response(&format!("could not scroll {}", c.output)),
+                }
+            },
+            Request::StopThread(c) => match self.handle_thread_command(&RenderThreadCommand::Stop(c.clone())) {
+                Ok(_) => Response::ok(format!("stopped the render thread for {}", c.output)),
+                Err(e) => e.to_response(&format!("could not stop {}", c.output)),
+            },
+            Request::Lock => {
+                self.handle_cmd(&CommandType::Dc(DaemonCommand::Lock));
+                Response::ok("command dispatched")
+            },
+            Request::Slideshow(c) => {
+                self.handle_cmd(&CommandType::Dc(DaemonCommand::Slideshow(c.clone())));
+                Response::ok("command dispatched")
+            },
+            Request::Reset(c) => match self.reset(c.output.as_ref()) {
+                Ok(redrawn) => match &c.output {
+                    Some(output) => Response::ok(format!("{output} is back to its config")),
+                    None => Response::ok(format!("back to the config ({} output(s) redrawn)", redrawn.len())),
+                },
+                Err(e) => e.to_response("could not reset"),
+            },
+            Request::History(c) => match self.history(&c.output) {
+                Ok(history) => Response::Ok(serde_json::to_value(&history).expect("could not serialize history")),
+                Err(e) => e.to_response("could not read history"),
+            },
+            Request::Undo(c) => match self.undo(&c.output) {
+                Ok(entry) => Response::ok(format!("{} is back to {}", c.output, entry.image)),
+                Err(e) => e.to_response("could not undo"),
+            },
+            Request::Stop | Request::EventStream => Response::ok("command dispatched"), // handled in process_ipc
+        }
+    }
+}
+
+// keeps slideshows whose source didn't change, so a config reload doesn't reset their position
+fn build_slideshows(config: &DaemonConfig, mut old: HashMap<String, Slideshow>) -> HashMap<String, Slideshow> {
+    let mut slideshows = HashMap::new();
+    for output in &config.outputs {
+        // a schedule decides what's on screen by itself
+        if !output.image.is_slideshow() || output.schedule.is_some() {
+            continue;
+        }
+        // one per group rather than one per member, so they stay on the same image
+        let key = config.group_of(&output.name).map_or(output.name.clone(), |g| g.name.clone());
+        if slideshows.contains_key(&key) {
+            continue;
+        }
+        let show = match old.remove(&key) {
+            Some(show) if show.source == output.image => show,
+            _ => Slideshow::new(output.image.clone()),
+        };
+        slideshows.insert(key, show);
+    }
+    slideshows
+}
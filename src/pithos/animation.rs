@@ -0,0 +1,91 @@
+// animated wallpapers (gif, apng, animated webp). frames come out of the decoders already composited onto the full
+// canvas, so each one can be scaled and handed to the compositor as-is.
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use std::time::Duration;
+
+use image::codecs::gif::GifDecoder;
+use image::codecs::png::PngDecoder;
+use image::codecs::webp::WebPDecoder;
+use image::{AnimationDecoder, Frame, Frames, ImageError, ImageFormat, ImageResult};
+
+// browsers bump tiny delays up to this, and plenty of gifs out there count on it
+const MIN_DELAY: Duration = Duration::from_millis(20);
+const DEFAULT_DELAY: Duration = Duration::from_millis(100);
+
+fn reader(path: &Path) -> ImageResult<BufReader<File>> {
+    Ok(BufReader::new(File::open(path).map_err(ImageError::IoError)?))
+}
+
+// more than one frame. only reads headers, except for gifs, which need their first two frames decoded to tell
+pub fn is_animated(path: &Path) -> bool {
+    let check = || -> ImageResult<bool> {
+        Ok(match super::decoders::image_format(path) {
+            Some(ImageFormat::Gif) => GifDecoder::new(reader(path)?)?.into_frames().take(2).count() > 1,
+            Some(ImageFormat::Png) => PngDecoder::new(reader(path)?)?.is_apng()?,
+            Some(ImageFormat::WebP) => WebPDecoder::new(reader(path)?)?.has_animation(),
+            _ => false, // also anything that isn't an image at all
+        })
+    };
+    check().unwrap_or(false)
+}
+
+pub fn frames(path: &Path) -> ImageResult<Frames<'static>> {
+    let format = match super::decoders::image_format(path) {
+        Some(format) => format,
+        None => return Err(ImageError::Unsupported(image::error::UnsupportedError::from_format_and_kind(
+            image::error::ImageFormatHint::PathExtension(path.to_path_buf()),
+            image::error::UnsupportedErrorKind::GenericFeature("animation".to_string()),
+        ))),
+    };
+    match format {
+        ImageFormat::Gif => Ok(GifDecoder::new(reader(path)?)?.into_frames()),
+        ImageFormat::Png => Ok(PngDecoder::new(reader(path)?)?.apng()?.into_frames()),
+        ImageFormat::WebP => Ok(WebPDecoder::new(reader(path)?)?.into_frames()),
+        format => Err(ImageError::Unsupported(image::error::UnsupportedError::from_format_and_kind(
+            format.into(),
+            image::error::UnsupportedErrorKind::GenericFeature("animation".to_string()),
+        ))),
+    }
+}
+
+pub fn frame_delay(frame: &Frame) -> Duration {
+    let (numer, denom) = frame.delay().numer_denom_ms();
+    if denom == 0 {
+        return DEFAULT_DELAY;
+    }
+    let delay = Duration::from_micros(numer as u64 * 1000 / denom as u64);
+    // 0 and 10ms delays mean "as fast as possible" to the encoder and "100ms" to every browser
+    if delay <= Duration::from_millis(10) {
+        return DEFAULT_DELAY;
+    }
+    delay.max(MIN_DELAY)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::codecs::gif::GifEncoder;
+    use image::{Delay, RgbaImage};
+
+    #[test]
+    fn reads_gif_frames_and_delays() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("anim.gif");
+        {
+            let mut encoder = GifEncoder::new(File::create(&path).unwrap());
+            for ms in [50, 0, 200] {
+                let frame = Frame::from_parts(RgbaImage::new(4, 4), 0, 0, Delay::from_numer_denom_ms(ms, 1));
+                encoder.encode_frame(frame).unwrap();
+            }
+        }
+        assert!(is_animated(&path));
+        let delays: Vec<Duration> = frames(&path).unwrap().map(|f| frame_delay(&f.unwrap())).collect();
+        assert_eq!(delays, vec![Duration::from_millis(50), DEFAULT_DELAY, Duration::from_millis(200)]);
+
+        let still = dir.path().join("still.png");
+        RgbaImage::new(4, 4).save(&still).unwrap();
+        assert!(!is_animated(&still));
+    }
+}
@@ -0,0 +1,151 @@
+// shared, fd-backed pixel buffers for render threads.
+// every render thread lives on its own wayland connection, so the WlShmPool/WlBuffer proxies can't be shared -
+// but the memory behind them can. threads showing the same image at the same scaled size get a dup of the same fd,
+// create their own pool from it, and the compositor ends up mapping the same pages for all of them.
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Error;
+use std::sync::Arc;
+use std::time::Duration;
+
+use pandora_ipc::status::{BufferCacheStats, BufferEntry};
+use wayrs_client::protocol::wl_shm::Format;
+
+use super::super::transform::Transform;
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct BufferKey {
+    pub image: String,
+    pub width: u32,
+    pub height: u32,
+    pub format: Format,
+    pub transform: Transform,
+    pub effects: String, // Effects::cache_id, empty for none
+}
+
+impl BufferKey {
+    // of a single frame
+    pub fn size_bytes(&self) -> u64 {
+        // only 32bpp formats for now
+        self.width as u64 * self.height as u64 * 4
+    }
+}
+
+// a render thread's handle on a cached buffer. must be handed back via BufferCache::release when the thread is done.
+// animations are every frame back to back in the one file, with `delays` saying how long each is shown
+pub struct SharedBuffer {
+    pub key: BufferKey,
+    pub id: u64,
+    pub file: File,
+    pub delays: Arc<Vec<Duration>>, // empty for stills
+}
+
+impl SharedBuffer {
+    pub fn frames(&self) -> usize {
+        self.delays.len().max(1)
+    }
+}
+
+struct Entry {
+    id: u64,
+    file: File,
+    delays: Arc<Vec<Duration>>,
+    refs: usize,
+}
+
+impl Entry {
+    fn size_bytes(&self, key: &BufferKey) -> u64 {
+        key.size_bytes() * self.delays.len().max(1) as u64
+    }
+}
+
+#[derive(Default)]
+pub struct BufferCache {
+    entries: HashMap<BufferKey, Entry>,
+    next_id: u64,
+}
+
+impl BufferCache {
+    // bumps the refcount and hands out a dup'd fd if we already have this buffer
+    pub fn acquire(&mut self, key: &BufferKey) -> Result<Option<SharedBuffer>, Error> {
+        match self.entries.get_mut(key) {
+            Some(entry) => {
+                let file = entry.file.try_clone()?;
+                entry.refs += 1;
+                Ok(Some(SharedBuffer { key: key.clone(), id: entry.id, file, delays: entry.delays.clone() }))
+            },
+            None => Ok(None),
+        }
+    }
+
+    // if another thread beat us to filling the same key, ours gets dropped and theirs is shared instead
+    pub fn insert(&mut self, key: BufferKey, file: File, delays: Vec<Duration>) -> Result<SharedBuffer, Error> {
+        if let Some(buf) = self.acquire(&key)? {
+            return Ok(buf);
+        }
+        self.next_id += 1;
+        let id = self.next_id;
+        let delays = Arc::new(delays);
+        let shared = SharedBuffer { key: key.clone(), id, file: file.try_clone()?, delays: delays.clone() };
+        self.entries.insert(key, Entry { id, file, delays, refs: 1 });
+        Ok(shared)
+    }
+
+    // forgets every buffer of an image, e.g. because the file changed. threads still holding one keep their fd
+    // (and their release becomes a no-op); anyone acquiring after this gets a freshly filled buffer
+    pub fn invalidate(&mut self, image: &str) -> usize {
+        let before = self.entries.len();
+        self.entries.retain(|key, _| key.image != image);
+        before - self.entries.len()
+    }
+
+    // returns true if this was the last user and the buffer got dropped
+    pub fn release(&mut self, buf: &SharedBuffer) -> bool {
+        let entry = match self.entries.get_mut(&buf.key) {
+            // ids differ if the entry was invalidated & refilled while this handle was out; nothing to do then
+            Some(entry) if entry.id == buf.id => entry,
+            _ => return false,
+        };
+        entry.refs -= 1;
+        if entry.refs == 0 {
+            self.entries.remove(&buf.key);
+            self.entries.shrink_to_fit();
+            return true;
+        }
+        false
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn size_bytes(&self) -> u64 {
+        self.entries.iter().map(|(k, e)| e.size_bytes(k)).sum()
+    }
+
+    pub fn stats(&self) -> BufferCacheStats {
+        BufferCacheStats {
+            entries: self.len(),
+            bytes: self.size_bytes(),
+            users: self.entries.values().map(|e| e.refs).sum(),
+        }
+    }
+
+    // biggest first
+    pub fn entries(&self) -> Vec<BufferEntry> {
+        let mut entries: Vec<BufferEntry> = self.entries.iter().map(|(k, e)| BufferEntry {
+            image: k.image.clone(),
+            width: k.width,
+            height: k.height,
+            frames: e.delays.len().max(1),
+            bytes: e.size_bytes(k),
+            users: e.refs,
+        }).collect();
+        entries.sort_by(|a, b| b.bytes.cmp(&a.bytes).then_with(|| a.image.cmp(&b.image)));
+        entries
+    }
+}
@@ -0,0 +1,185 @@
+// persistent cache of pre-scaled images, stored as raw BGRA (wl_shm argb8888) so a hit can be handed
+// straight to the compositor as the pool fd - no decode, no resize.
+// entries are keyed on everything that would change the pixels: source path + mtime + size, target size, filter.
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::io::{Error, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use pandora_ipc::status::DiskCacheUsage;
+
+const EXTENSION: &str = "bgra";
+// bumped whenever decoding starts producing different pixels for the same file (orientation, color conversion,
+// premultiplied alpha..)
+// so entries written by older builds stop matching
+const PIXELS_VERSION: u8 = 2;
+
+pub fn get_cache_dir() -> PathBuf {
+    let base_dir = match env::var("XDG_CACHE_HOME") {
+        Ok(s) => shellexpand::full(&s).unwrap().into_owned(),
+        Err(_) => shellexpand::full("~/.cache").unwrap().into_owned(),
+    };
+    Path::new(&base_dir).join("pandora")
+}
+
+// the std hasher isn't guaranteed stable between toolchains, and we'd like the cache to survive a rebuild
+fn fnv1a(bytes: &[u8], mut hash: u64) -> u64 {
+    for b in bytes {
+        hash ^= *b as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+#[derive(Clone, Debug)]
+pub struct DiskCache {
+    dir: PathBuf,
+    max_bytes: u64, // 0 disables the cache entirely
+}
+
+impl DiskCache {
+    pub fn new(dir: PathBuf, max_bytes: u64) -> DiskCache {
+        DiskCache { dir, max_bytes }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.max_bytes > 0
+    }
+
+    pub fn max_bytes(&self) -> u64 {
+        self.max_bytes
+    }
+
+    pub fn set_max_bytes(&mut self, max_bytes: u64) {
+        self.max_bytes = max_bytes;
+    }
+
+    // file name for a given source + target geometry. errors if the source can't be stat'd
+    pub fn key(source: &Path, width: u32, height: u32, filter: &str) -> Result<String, Error> {
+        let meta = fs::metadata(source)?;
+        let mtime = meta.modified()?.duration_since(UNIX_EPOCH).map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+        let mut hash = 0xcbf29ce484222325;
+        hash = fnv1a(source.as_os_str().as_encoded_bytes(), hash);
+        hash = fnv1a(&mtime.as_nanos().to_le_bytes(), hash);
+        hash = fnv1a(&meta.len().to_le_bytes(), hash);
+        hash = fnv1a(filter.as_bytes(), hash);
+        hash = fnv1a(&[PIXELS_VERSION], hash);
+        Ok(format!("{hash:016x}-{width}x{height}.{EXTENSION}"))
+    }
+
+    // returns an fd to a cached buffer of exactly `expected_len` bytes, if we have one
+    pub fn get(&self, name: &str, expected_len: u64) -> Option<File> {
+        if !self.enabled() {
+            return None;
+        }
+        let path = self.dir.join(name);
+        // opened rw because wl_shm pools get mapped shared/writable on the compositor's side
+        let file = OpenOptions::new().read(true).write(true).open(&path).ok()?;
+        match file.metadata() {
+            Ok(meta) if meta.len() == expected_len => (),
+            _ => {
+                // truncated by a crash mid-write or some such. not worth trusting
+                let _ = fs::remove_file(&path);
+                return None;
+            },
+        }
+        // mtime doubles as the last-used stamp for eviction
+        let _ = file.set_modified(SystemTime::now());
+        Some(file)
+    }
+
+    // writes a new entry via `fill`, then atomically moves it into place. the returned fd stays valid even if the
+    // entry gets evicted later on, so it's fine to hand to a render thread directly.
+    pub fn put<F, E>(&self, name: &str, fill: F) -> Result<File, E>
+    where F: FnOnce(&File) -> Result<(), E>, E: From<Error> {
+        fs::create_dir_all(&self.dir)?;
+        let tmp = tempfile::NamedTempFile::new_in(&self.dir)?;
+        fill(tmp.as_file())?;
+        let file = tmp.persist(self.dir.join(name)).map_err(Error::from)?;
+        self.enforce_limit()?;
+        Ok(file)
+    }
+
+    fn entries(&self) -> Result<Vec<(PathBuf, u64, SystemTime)>, Error> {
+        let mut entries = Vec::new();
+        let dir = match fs::read_dir(&self.dir) {
+            Ok(dir) => dir,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(entries),
+            Err(e) => return Err(e),
+        };
+        for entry in dir {
+            let path = entry?.path();
+            if path.extension().is_none_or(|ext| ext != EXTENSION) {
+                continue;
+            }
+            let meta = fs::metadata(&path)?;
+            entries.push((path, meta.len(), meta.modified()?));
+        }
+        Ok(entries)
+    }
+
+    // drops least-recently-used entries until we're back under the limit
+    pub fn enforce_limit(&self) -> Result<(), Error> {
+        let mut entries = self.entries()?;
+        let mut total: u64 = entries.iter().map(|e| e.1).sum();
+        entries.sort_by_key(|e| e.2);
+        for (path, len, _) in entries {
+            if total <= self.max_bytes {
+                break;
+            }
+            fs::remove_file(path)?;
+            total -= len;
+        }
+        Ok(())
+    }
+
+    pub fn usage(&self) -> Result<DiskCacheUsage, Error> {
+        let entries = self.entries()?;
+        Ok(DiskCacheUsage {
+            files: entries.len(),
+            bytes: entries.iter().map(|e| e.1).sum(),
+        })
+    }
+
+    pub fn clear(&self) -> Result<DiskCacheUsage, Error> {
+        let usage = self.usage()?;
+        for (path, _, _) in self.entries()? {
+            fs::remove_file(path)?;
+        }
+        Ok(usage)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn key_depends_on_geometry_and_filter() {
+        let src = tempfile::NamedTempFile::new().unwrap();
+        let key = DiskCache::key(src.path(), 1920, 1080, "Lanczos3").unwrap();
+        assert_eq!(key, DiskCache::key(src.path(), 1920, 1080, "Lanczos3").unwrap());
+        assert_ne!(key, DiskCache::key(src.path(), 1080, 1920, "Lanczos3").unwrap());
+        assert_ne!(key, DiskCache::key(src.path(), 1920, 1080, "Nearest").unwrap());
+    }
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = DiskCache::new(dir.path().to_path_buf(), 8);
+        let fill = |mut f: &File| f.write_all(&[0; 4]);
+        cache.put("a.bgra", fill).unwrap();
+        cache.put("b.bgra", fill).unwrap();
+        // touching a makes b the oldest
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        assert!(cache.get("a.bgra", 4).is_some());
+        cache.put("c.bgra", fill).unwrap();
+        assert!(cache.get("a.bgra", 4).is_some());
+        assert!(cache.get("b.bgra", 4).is_none());
+        assert!(cache.get("c.bgra", 4).is_some());
+        // wrong length is treated as a miss
+        assert!(cache.get("c.bgra", 8).is_none());
+    }
+}
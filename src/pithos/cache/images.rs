@@ -0,0 +1,203 @@
+// decoded, full-resolution source images. these are big (an 8k image is ~128MiB of rgba), so:
+// - the cache is LRU-evicted down to a memory budget
+// - anything pinned (mid-scale for an output, needed by the lockscreen) is exempt from eviction
+// - once a scaled copy exists, the original is dropped unless something still has it pinned
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use image::RgbaImage;
+use pandora_ipc::status::{ImageCacheStats, ImageEntry};
+
+struct Entry {
+    image: Arc<RgbaImage>,
+    last_used: u64,
+}
+
+#[derive(Default)]
+pub struct ImageCache {
+    entries: HashMap<String, Entry>,
+    pins: HashMap<String, usize>,
+    budget_bytes: u64,
+    tick: u64,
+    hits: u64,
+    misses: u64,
+    evictions: u64,
+}
+
+fn image_bytes(image: &RgbaImage) -> u64 {
+    image.as_raw().len() as u64
+}
+
+impl ImageCache {
+    pub fn new(budget_bytes: u64) -> ImageCache {
+        ImageCache { budget_bytes, ..Default::default() }
+    }
+
+    pub fn contains(&self, path: &str) -> bool {
+        self.entries.contains_key(path)
+    }
+
+    // doesn't count as a use
+    pub fn dimensions(&self, path: &str) -> Option<(u32, u32)> {
+        self.entries.get(path).map(|e| e.image.dimensions())
+    }
+
+    pub fn get(&mut self, path: &str) -> Option<Arc<RgbaImage>> {
+        self.tick += 1;
+        match self.entries.get_mut(path) {
+            Some(entry) => {
+                self.hits += 1;
+                entry.last_used = self.tick;
+                Some(entry.image.clone())
+            },
+            None => {
+                self.misses += 1;
+                None
+            },
+        }
+    }
+
+    // returns whatever got evicted to make room
+    pub fn insert(&mut self, path: String, image: RgbaImage) -> Vec<String> {
+        self.tick += 1;
+        self.entries.insert(path, Entry { image: Arc::new(image), last_used: self.tick });
+        self.evict()
+    }
+
+    pub fn remove(&mut self, path: &str) -> bool {
+        let removed = self.entries.remove(path).is_some();
+        if removed {
+            self.entries.shrink_to_fit();
+        }
+        removed
+    }
+
+    // for once the image has been scaled for whoever needed it
+    pub fn drop_original(&mut self, path: &str) -> bool {
+        if self.is_pinned(path) {
+            return false;
+        }
+        self.remove(path)
+    }
+
+    pub fn pin(&mut self, path: &str) {
+        *self.pins.entry(path.to_string()).or_insert(0) += 1;
+    }
+
+    pub fn unpin(&mut self, path: &str) -> Vec<String> {
+        if let Some(count) = self.pins.get_mut(path) {
+            *count -= 1;
+            if *count == 0 {
+                self.pins.remove(path);
+            }
+        }
+        self.evict()
+    }
+
+    pub fn is_pinned(&self, path: &str) -> bool {
+        self.pins.contains_key(path)
+    }
+
+    pub fn set_budget(&mut self, budget_bytes: u64) -> Vec<String> {
+        self.budget_bytes = budget_bytes;
+        self.evict()
+    }
+
+    pub fn size_bytes(&self) -> u64 {
+        self.entries.values().map(|e| image_bytes(&e.image)).sum()
+    }
+
+    // least recently used first, skipping pinned images. pinned images can push us over budget; that's on purpose
+    fn evict(&mut self) -> Vec<String> {
+        let mut evicted = Vec::new();
+        let mut total = self.size_bytes();
+        if total <= self.budget_bytes {
+            return evicted;
+        }
+        let mut candidates: Vec<(u64, String, u64)> = self.entries.iter()
+            .filter(|(path, _)| !self.pins.contains_key(*path))
+            .map(|(path, e)| (e.last_used, path.clone(), image_bytes(&e.image)))
+            .collect();
+        candidates.sort();
+        for (_, path, bytes) in candidates {
+            if total <= self.budget_bytes {
+                break;
+            }
+            self.entries.remove(&path);
+            self.evictions += 1;
+            total -= bytes;
+            evicted.push(path);
+        }
+        self.entries.shrink_to_fit();
+        evicted
+    }
+
+    pub fn stats(&self) -> ImageCacheStats {
+        let mut pinned: Vec<String> = self.pins.keys().cloned().collect();
+        pinned.sort();
+        ImageCacheStats {
+            entries: self.entries.len(),
+            bytes: self.size_bytes(),
+            budget_bytes: self.budget_bytes,
+            pinned,
+            hits: self.hits,
+            misses: self.misses,
+            evictions: self.evictions,
+        }
+    }
+
+    // biggest first
+    pub fn entries(&self) -> Vec<ImageEntry> {
+        let mut entries: Vec<ImageEntry> = self.entries.iter().map(|(path, e)| ImageEntry {
+            path: path.clone(),
+            width: e.image.width(),
+            height: e.image.height(),
+            bytes: image_bytes(&e.image),
+            pinned: self.is_pinned(path),
+        }).collect();
+        entries.sort_by(|a, b| b.bytes.cmp(&a.bytes).then_with(|| a.path.cmp(&b.path)));
+        entries
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 4 bytes a pixel, so these are 400 bytes each
+    fn img() -> RgbaImage {
+        RgbaImage::new(10, 10)
+    }
+
+    #[test]
+    fn evicts_lru_but_not_pinned() {
+        let mut cache = ImageCache::new(1000);
+        cache.insert("a".to_string(), img());
+        cache.insert("b".to_string(), img());
+        cache.pin("a");
+        cache.get("a");
+        cache.get("b");
+        // a is older but pinned, so b goes
+        assert_eq!(cache.insert("c".to_string(), img()), vec!["b".to_string()]);
+        assert!(cache.contains("a"));
+        cache.insert("d".to_string(), img());
+        assert!(cache.contains("a"));
+        // unpinning puts a back up for grabs
+        cache.unpin("a");
+        assert_eq!(cache.set_budget(400), vec!["a".to_string()]);
+        assert_eq!(cache.stats().evictions, 3);
+    }
+
+    #[test]
+    fn drop_original_respects_pins() {
+        let mut cache = ImageCache::new(u64::MAX);
+        cache.insert("a".to_string(), img());
+        cache.pin("a");
+        cache.pin("a");
+        assert!(!cache.drop_original("a"));
+        cache.unpin("a");
+        assert!(!cache.drop_original("a"));
+        cache.unpin("a");
+        assert!(cache.drop_original("a"));
+    }
+}
@@ -0,0 +1,3 @@
+pub mod buffers;
+pub mod disk;
+pub mod images;
@@ -0,0 +1,166 @@
+// color management for decoded images. an embedded icc profile means the pixels aren't sRGB (wide gamut phone photos,
+// adobe rgb exports, hdr stills..). if the compositor can be told what they are (wp_color_management_v1, negotiated by
+// the outputs thread) and it's one of the few spaces we know by name, the pixels are left alone and the surface gets
+// tagged instead; anything else is converted to sRGB so it at least doesn't come out oversaturated.
+// a profile we can't make sense of just leaves the image alone: off colors beat no wallpaper.
+use image::{ColorType, DynamicImage, ImageBuffer};
+use moxcms::{CicpColorPrimaries, CmsError, ColorProfile, DataColorSpace, Layout, ToneReprCurve, TransferCharacteristics, TransformOptions, Xyzd};
+use wayrs_protocols::color_management_v1::wp_color_manager_v1::{Primaries, TransferFunction};
+
+// the spaces we can name to the compositor. buffers are still 8 bit argb, so pq content is coarser than it could be
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+pub enum ColorSpace {
+    Srgb,
+    DisplayP3,
+    Bt2020Pq,
+}
+
+impl ColorSpace {
+    pub const ALL: [ColorSpace; 3] = [ColorSpace::Srgb, ColorSpace::DisplayP3, ColorSpace::Bt2020Pq];
+
+    pub fn primaries(&self) -> Primaries {
+        match self {
+            ColorSpace::Srgb => Primaries::Srgb,
+            ColorSpace::DisplayP3 => Primaries::DisplayP3,
+            ColorSpace::Bt2020Pq => Primaries::Bt2020,
+        }
+    }
+
+    pub fn transfer(&self) -> TransferFunction {
+        match self {
+            ColorSpace::Srgb | ColorSpace::DisplayP3 => TransferFunction::Srgb,
+            ColorSpace::Bt2020Pq => TransferFunction::St2084Pq,
+        }
+    }
+
+    fn profile(&self) -> ColorProfile {
+        match self {
+            ColorSpace::Srgb => ColorProfile::new_srgb(),
+            ColorSpace::DisplayP3 => ColorProfile::new_display_p3(),
+            ColorSpace::Bt2020Pq => ColorProfile::new_bt2020_pq(),
+        }
+    }
+}
+
+fn close(a: &Xyzd, b: &Xyzd) -> bool {
+    (a.x - b.x).abs() < 0.002 && (a.y - b.y).abs() < 0.002 && (a.z - b.z).abs() < 0.002
+}
+
+fn srgb_curve(trc: &Option<ToneReprCurve>) -> bool {
+    let reference = [2.4, 1.0 / 1.055, 0.055 / 1.055, 1.0 / 12.92, 0.04045];
+    match trc {
+        Some(ToneReprCurve::Parametric(params)) => params.len() >= 5
+            && params.iter().zip(reference).all(|(p, r)| (p - r).abs() < 0.001),
+        _ => false,
+    }
+}
+
+// which named space an icc profile describes, if any. a cicp tag usually says outright; otherwise the colorants and curves
+// have to match (apple's display p3 profile, the usual srgb ones..)
+pub fn identify(icc: &[u8]) -> Option<ColorSpace> {
+    let profile = ColorProfile::new_from_slice(icc).ok()?;
+    let named = profile.cicp.as_ref().and_then(|cicp| match (cicp.color_primaries, cicp.transfer_characteristics) {
+        (CicpColorPrimaries::Bt709, TransferCharacteristics::Srgb) => Some(ColorSpace::Srgb),
+        (CicpColorPrimaries::Smpte432, TransferCharacteristics::Srgb) => Some(ColorSpace::DisplayP3),
+        (CicpColorPrimaries::Bt2020, TransferCharacteristics::Smpte2084) => Some(ColorSpace::Bt2020Pq),
+        _ => None, // not always trustworthy (moxcms writes display p3 as dci-p3), so check the colorants anyway
+    });
+    if named.is_some() {
+        return named;
+    }
+    if profile.color_space != DataColorSpace::Rgb || ![&profile.red_trc, &profile.green_trc, &profile.blue_trc].into_iter().all(srgb_curve) {
+        return None;
+    }
+    [ColorSpace::Srgb, ColorSpace::DisplayP3].into_iter().find(|space| {
+        let named = space.profile();
+        close(&profile.red_colorant, &named.red_colorant)
+            && close(&profile.green_colorant, &named.green_colorant)
+            && close(&profile.blue_colorant, &named.blue_colorant)
+    })
+}
+
+// what an image with this profile ends up as, given the spaces the compositor takes
+pub fn target(icc: Option<&[u8]>, supported: &[ColorSpace]) -> ColorSpace {
+    match icc.and_then(identify) {
+        Some(space) if supported.contains(&space) => space,
+        _ => ColorSpace::Srgb,
+    }
+}
+
+pub fn prepare(img: DynamicImage, icc: Option<&[u8]>, supported: &[ColorSpace]) -> (DynamicImage, ColorSpace) {
+    let space = target(icc, supported);
+    // already what we'd be converting to
+    if icc.is_none() || icc.and_then(identify) == Some(space) {
+        return (img, space);
+    }
+    (to_srgb(img, icc), ColorSpace::Srgb)
+}
+
+fn to_srgb(img: DynamicImage, icc: Option<&[u8]>) -> DynamicImage {
+    let profile = match icc.map(ColorProfile::new_from_slice) {
+        Some(Ok(profile)) => profile,
+        _ => return img,
+    };
+    match convert(&img, &profile, &ColorSpace::Srgb.profile()) {
+        Ok(converted) => converted,
+        Err(_) => img,
+    }
+}
+
+// 16 bit (and float) sources go through a 16 bit transform so gradients don't pick up banding on the way
+pub fn convert(img: &DynamicImage, from: &ColorProfile, to: &ColorProfile) -> Result<DynamicImage, CmsError> {
+    // cmyk jpegs are already rgb by the time the decoder hands them over, so their profile no longer applies
+    let src_layout = match from.color_space {
+        DataColorSpace::Rgb => Layout::Rgba,
+        DataColorSpace::Gray => Layout::GrayAlpha,
+        _ => return Err(CmsError::UnsupportedProfileConnection),
+    };
+    let (width, height) = (img.width(), img.height());
+    let options = TransformOptions::default();
+    let deep = matches!(img.color(), ColorType::L16 | ColorType::La16 | ColorType::Rgb16 | ColorType::Rgba16 | ColorType::Rgb32F | ColorType::Rgba32F);
+    if deep {
+        let src = match src_layout {
+            Layout::GrayAlpha => img.to_luma_alpha16().into_raw(),
+            _ => img.to_rgba16().into_raw(),
+        };
+        let mut dst = vec![0u16; width as usize * height as usize * 4];
+        from.create_transform_16bit(src_layout, to, Layout::Rgba, options)?.transform(&src, &mut dst)?;
+        let buf = ImageBuffer::from_raw(width, height, dst).ok_or(CmsError::LaneSizeMismatch)?;
+        Ok(DynamicImage::ImageRgba16(buf))
+    } else {
+        let src = match src_layout {
+            Layout::GrayAlpha => img.to_luma_alpha8().into_raw(),
+            _ => img.to_rgba8().into_raw(),
+        };
+        let mut dst = vec![0u8; width as usize * height as usize * 4];
+        from.create_transform_8bit(src_layout, to, Layout::Rgba, options)?.transform(&src, &mut dst)?;
+        let buf = ImageBuffer::from_raw(width, height, dst).ok_or(CmsError::LaneSizeMismatch)?;
+        Ok(DynamicImage::ImageRgba8(buf))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display_p3_lands_in_srgb() {
+        // sRGB's pure red, as display p3 writes it down
+        let p3_red = image::RgbaImage::from_pixel(2, 2, image::Rgba([234, 51, 35, 255]));
+        let converted = convert(&p3_red.into(), &ColorSpace::DisplayP3.profile(), &ColorSpace::Srgb.profile()).unwrap();
+        let px = converted.to_rgba8().get_pixel(1, 1).0;
+        assert!(px[0] >= 250 && px[1] <= 8 && px[2] <= 8, "{px:?}");
+        assert_eq!(px[3], 255);
+    }
+
+    #[test]
+    fn names_known_profiles() {
+        let p3 = ColorSpace::DisplayP3.profile().encode().unwrap();
+        let srgb = ColorSpace::Srgb.profile().encode().unwrap();
+        assert_eq!(identify(&p3), Some(ColorSpace::DisplayP3));
+        assert_eq!(identify(&srgb), Some(ColorSpace::Srgb));
+        // p3 pixels stay put only if the compositor can be told about them
+        assert_eq!(target(Some(&p3), &ColorSpace::ALL), ColorSpace::DisplayP3);
+        assert_eq!(target(Some(&p3), &[ColorSpace::Srgb]), ColorSpace::Srgb);
+    }
+}
@@ -1,41 +1,8 @@
 use serde::{Serialize, Deserialize};
 use super::config::DaemonConfig;
-// ===== TRAITS AND MISC DATA STRUCTS =====
-#[derive(knuffel::DecodeScalar, Serialize, Deserialize, PartialEq, Copy, Clone, Debug)]
-pub enum RenderMode {
-    // single image
-    Static, // will scale up/down to fill
-    ScrollVertical,
-    ScrollLateral,
-    // scrolling both directions will be trickier to implement. later problem.
-    // hello from later me: honestly it's probably easier than I thought:
-    // the agent can enforce positional state well, & correcting-on-the-fly looks better than expected
-}
-
-// ===== COMMAND STRUCTS =====
-#[derive(Serialize, Deserialize, Clone, Debug)]
-pub struct LoadImageCommand {
-    pub image: String,
-}
-
-#[derive(Serialize, Deserialize, Clone, Debug)]
-pub struct RenderCommand {
-    pub output: String,
-    pub image: String,
-    pub mode: RenderMode,
-}
-
-#[derive(Serialize, Deserialize, Clone, Debug)]
-#[derive(clap::Args)]
-pub struct StopCommand {
-    pub output: String,
-}
-
-#[derive(Serialize, Deserialize, Clone, Debug)]
-pub struct ScrollCommand {
-    pub output: String,
-    pub position: u32,
-}
+// the daemon's own messages between its threads. what clients send lives in pandora_ipc; the bits of it that get
+// passed around inside unchanged are re-exported from here
+pub use pandora_ipc::commands::{LoadImageCommand, RenderCommand, RenderMode, ScrollCommand, SlideshowAction, SlideshowCommand, StopCommand};
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct ModeCommand {
@@ -59,6 +26,9 @@ pub enum DaemonCommand {
     ReloadConfig(DaemonConfig),
     OutputModeChange(ModeCommand),
     Stop,
+    Slideshow(SlideshowCommand),
+    // a render thread was (re)pointed at an image, so compositor agents can redo their scroll math
+    ImageChanged(RenderCommand),
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
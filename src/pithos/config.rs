@@ -1,6 +1,12 @@
-use std::{cmp::Ordering, env, fs, path::{Path, PathBuf}, thread, time::Duration};
+use std::{cmp::Ordering, env, fs, path::{Path, PathBuf}, sync::{Mutex, MutexGuard}, thread, time::Duration};
 
 use super::commands::RenderMode;
+use super::decoders;
+use super::effects::{Color, Number};
+use super::error::DaemonError;
+use super::packages::{is_gnome_background, is_kde_package};
+use super::schedule::ScheduleTime;
+use super::transform::Transform;
 
 #[derive(Copy, Clone, Debug, Default)]
 #[derive(Eq, Ord, PartialEq, PartialOrd)]
@@ -23,7 +29,11 @@ impl LogLevel {
 #[derive(Clone, Debug, knuffel::Decode, serde::Serialize, serde::Deserialize)]
 pub enum ConfigNode {
     Output(OutputConfig),
+    OutputGroup(OutputGroupConfig),
     Logging(#[knuffel(argument)] LogLevel),
+    Cache(CacheConfig),
+    Location(Location),
+    Ipc(IpcConfig),
 }
 
 #[derive(Clone, Debug, knuffel::DecodeScalar, serde::Serialize, serde::Deserialize)]
@@ -37,14 +47,121 @@ pub enum LockRenderMode {
     Static,
 }
 
+#[derive(Copy, Clone, Debug, PartialEq, knuffel::DecodeScalar, serde::Serialize, serde::Deserialize)]
+pub enum SlideshowOrder {
+    Sequential,
+    Shuffle,
+    ShuffleNoRepeat, // every image once per round
+}
+
+/// image "~/pictures/wallpapers/portrait.png"
+/// image "~/pictures/wallpapers/" interval=600 order="shuffle-no-repeat" recursive=true
+/// image "~/pictures/wallpapers/*.png" interval=300
+/// image "/usr/share/backgrounds/gnome/adwaita-timed.xml"
+/// image "/usr/share/wallpapers/Next" // kde wallpaper package
+/// a single file, a directory/glob to rotate through every `interval` seconds, or another desktop's wallpaper package
+/// image "~/pictures/wallpapers/panorama.png" {
+///     crop 3000 0 1440 2560   // x y width height, in the image's pixels
+///     rotate 90               // clockwise; 90, 180 or 270
+///     flip "horizontal"       // or "vertical"
+/// }
+/// these apply to whatever the output ends up showing, and always in that order
+#[derive(Clone, Debug, Default, PartialEq, knuffel::Decode, serde::Serialize, serde::Deserialize)]
+pub struct ImageSource {
+    #[knuffel(argument)]
+    pub path: String,
+    #[knuffel(property)]
+    pub interval: Option<u64>,
+    #[knuffel(property)]
+    pub order: Option<SlideshowOrder>,
+    #[knuffel(property)]
+    pub recursive: Option<bool>,
+    #[knuffel(child)]
+    pub crop: Option<Crop>,
+    #[knuffel(child, unwrap(argument))]
+    pub rotate: Option<u32>,
+    #[knuffel(child, unwrap(argument))]
+    pub flip: Option<Flip>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, knuffel::Decode, serde::Serialize, serde::Deserialize)]
+pub struct Crop {
+    #[knuffel(argument)]
+    pub x: u32,
+    #[knuffel(argument)]
+    pub y: u32,
+    #[knuffel(argument)]
+    pub width: u32,
+    #[knuffel(argument)]
+    pub height: u32,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, knuffel::DecodeScalar, serde::Serialize, serde::Deserialize)]
+pub enum Flip {
+    Horizontal,
+    Vertical,
+}
+
+impl ImageSource {
+    pub fn is_slideshow(&self) -> bool {
+        self.path.contains(['*', '?', '[']) || (Path::new(&self.path).is_dir() && !self.is_package())
+    }
+
+    pub fn is_package(&self) -> bool {
+        let path = Path::new(&self.path);
+        is_kde_package(path) || is_gnome_background(path)
+    }
+
+    pub fn transform(&self) -> Transform {
+        Transform { crop: self.crop, rotate: self.rotate.unwrap_or(0) % 360, flip: self.flip, region: None }
+    }
+}
+
+/// location latitude=52.37 longitude=4.90
+/// only used to work out sunrise/sunset for schedules; never leaves the machine
+#[derive(Copy, Clone, Debug, PartialEq, knuffel::Decode, serde::Serialize, serde::Deserialize)]
+pub struct Location {
+    #[knuffel(property)]
+    pub latitude: f64, // north positive
+    #[knuffel(property)]
+    pub longitude: f64, // east positive
+}
+
+/// schedule {
+///     at "07:00" "~/pictures/wallpapers/morning.png"
+///     at "sunset" "~/pictures/wallpapers/dusk.png" offset=-30
+/// }
+/// times are HH:MM or dawn/sunrise/noon/sunset/dusk (which need a `location`), offset is in minutes
+#[derive(Clone, Debug, Default, PartialEq, knuffel::Decode, serde::Serialize, serde::Deserialize)]
+pub struct ScheduleConfig {
+    #[knuffel(children(name="at"))]
+    pub entries: Vec<ScheduleEntry>,
+}
+
+#[derive(Clone, Debug, PartialEq, knuffel::Decode, serde::Serialize, serde::Deserialize)]
+pub struct ScheduleEntry {
+    #[knuffel(argument, str)]
+    pub time: ScheduleTime,
+    #[knuffel(argument)]
+    pub image: String,
+    #[knuffel(property)]
+    pub offset: Option<i64>,
+}
+
 #[derive(Clone, Debug, Default, knuffel::Decode, serde::Serialize, serde::Deserialize)]
 pub struct OutputConfig {
     #[knuffel(argument)]
     pub name: String,
-    #[knuffel(child, unwrap(argument))]
-    pub image: String,
+    #[knuffel(child, default)]
+    pub image: ImageSource, // fallback for when a schedule has nothing to say
+    #[knuffel(child)]
+    pub schedule: Option<ScheduleConfig>,
     #[knuffel(child, unwrap(argument))]
     pub mode: Option<RenderMode>,
+    #[knuffel(child, default)]
+    pub effects: Effects,
+    #[knuffel(children(name="layer"))]
+    pub layers: Vec<LayerConfig>,
     // sub-items
     #[knuffel(child)]
     pub lockscreen: Option<LockConfig>,
@@ -52,6 +169,70 @@ pub struct OutputConfig {
     pub workspaces: Option<Vec<WorkspaceConfig>>,
 }
 
+/// layer "~/pictures/wallpapers/foreground.png" alpha=0.9 scroll=1.0
+/// stacked over the output's image (and each other, in order) as their own subsurfaces. `scroll` is how far through
+/// its own height a layer gets for every bit of the way the output's image scrolls: 1 keeps pace, 0.3 lags well behind
+#[derive(Clone, Debug, PartialEq, knuffel::Decode, serde::Serialize, serde::Deserialize)]
+pub struct LayerConfig {
+    #[knuffel(argument)]
+    pub image: String,
+    #[knuffel(property)]
+    pub alpha: Option<Number>,
+    #[knuffel(property)]
+    pub scroll: Option<Number>,
+}
+
+impl LayerConfig {
+    pub fn scroll_factor(&self) -> f64 {
+        self.scroll.map_or(1.0, |n| n.0.max(0.0) as f64)
+    }
+
+    // how its pixels get written out: just faded, the output's effects are for the output's own image
+    pub fn effects(&self) -> Effects {
+        Effects { opacity: self.alpha, ..Effects::default() }
+    }
+}
+
+/// output-group "desk" {
+///     outputs "DP-1" "DP-2" "DP-3"
+///     image "~/pictures/wallpapers/panorama.png"
+///     mode "static"
+///     bezel 40 // logical pixels of image hidden behind the frames between neighbouring outputs
+/// }
+/// one image across several outputs, cut up by where the compositor has them laid out. each member acts like an
+/// `output` with these settings, so members can't have an `output` stanza of their own
+#[derive(Clone, Debug, Default, PartialEq, knuffel::Decode, serde::Serialize, serde::Deserialize)]
+pub struct OutputGroupConfig {
+    #[knuffel(argument)]
+    pub name: String,
+    #[knuffel(child, unwrap(arguments))]
+    pub outputs: Vec<String>,
+    #[knuffel(child, default)]
+    pub image: ImageSource,
+    #[knuffel(child)]
+    pub schedule: Option<ScheduleConfig>,
+    #[knuffel(child, unwrap(argument))]
+    pub mode: Option<RenderMode>,
+    #[knuffel(child, unwrap(argument))]
+    pub bezel: Option<u32>,
+    #[knuffel(child, default)]
+    pub effects: Effects,
+}
+
+impl OutputGroupConfig {
+    // the `output` each member stands in as
+    fn member_config(&self, output: &str) -> OutputConfig {
+        OutputConfig {
+            name: output.to_string(),
+            image: self.image.clone(),
+            schedule: self.schedule.clone(),
+            mode: self.mode,
+            effects: self.effects.clone(),
+            ..OutputConfig::default()
+        }
+    }
+}
+
 #[derive(Clone, Debug, knuffel::Decode, serde::Serialize, serde::Deserialize)]
 pub struct LockConfig {
     #[knuffel(child, unwrap(argument))]
@@ -75,6 +256,103 @@ pub struct WorkspaceConfig {
     pub mode: Option<RenderMode>,
     #[knuffel(child, unwrap(arguments))]
     pub trigger: Vec<ConfigTriggers>,
+    #[knuffel(child)]
+    pub schedule: Option<ScheduleConfig>,
+    #[knuffel(child, default)]
+    pub effects: Effects,
+}
+
+/// effects {
+///     blur 8              // sigma, in output pixels
+///     brightness 0.8      // multiplier
+///     saturate 1.2        // 0 is greyscale, 1 leaves it be
+///     tint "#223344" 0.1  // color, then how much of it (0-1)
+///     opacity 0.8         // only shows through on `layer`s, there's nothing behind an output's own image
+/// }
+#[derive(Clone, Debug, Default, PartialEq, knuffel::Decode, serde::Serialize, serde::Deserialize)]
+pub struct Effects {
+    #[knuffel(child, unwrap(argument))]
+    pub blur: Option<Number>,
+    #[knuffel(child, unwrap(argument))]
+    pub brightness: Option<Number>,
+    #[knuffel(child, unwrap(argument))]
+    pub saturate: Option<Number>,
+    #[knuffel(child)]
+    pub tint: Option<Tint>,
+    #[knuffel(child, unwrap(argument))]
+    pub opacity: Option<Number>,
+}
+
+#[derive(Clone, Debug, PartialEq, knuffel::Decode, serde::Serialize, serde::Deserialize)]
+pub struct Tint {
+    #[knuffel(argument, str)]
+    pub color: Color,
+    #[knuffel(argument)]
+    pub amount: Number,
+}
+
+/// cache {
+///     disk-limit-mb 1024 // 0 disables the on-disk cache of pre-scaled images
+///     memory-limit-mb 512 // decoded full-size images; ones in use can go over this
+/// }
+#[derive(Clone, Debug, Default, knuffel::Decode, serde::Serialize, serde::Deserialize)]
+pub struct CacheConfig {
+    #[knuffel(child, unwrap(argument))]
+    pub disk_limit_mb: Option<u64>,
+    #[knuffel(child, unwrap(argument))]
+    pub memory_limit_mb: Option<u64>,
+}
+
+impl CacheConfig {
+    pub fn disk_limit_bytes(&self) -> u64 {
+        self.disk_limit_mb.unwrap_or(1024) * 1024 * 1024
+    }
+    pub fn memory_limit_bytes(&self) -> u64 {
+        self.memory_limit_mb.unwrap_or(512) * 1024 * 1024
+    }
+}
+
+impl DaemonConfig {
+    pub fn lockscreen_images(&self) -> Vec<String> {
+        self.outputs.iter()
+            .filter_map(|o| o.lockscreen.as_ref().map(|l| l.image.clone()))
+            .collect()
+    }
+
+    pub fn group_of(&self, output: &str) -> Option<&OutputGroupConfig> {
+        self.groups.iter().find(|g| g.outputs.iter().any(|o| o == output))
+    }
+
+    pub fn schedules(&self) -> impl Iterator<Item = &ScheduleConfig> {
+        self.outputs.iter().filter_map(|o| o.schedule.as_ref())
+    }
+
+    // every image file the config refers to, deduplicated. slideshow directories/globs aren't included
+    pub fn image_paths(&self) -> Vec<String> {
+        let mut paths = self.lockscreen_images();
+        for output in &self.outputs {
+            if !output.image.is_slideshow() && !output.image.is_package() {
+                paths.push(output.image.path.clone());
+            }
+            paths.extend(output.schedule.iter().flat_map(|s| s.entries.iter().map(|e| e.image.clone())));
+            paths.extend(output.layers.iter().map(|l| l.image.clone()));
+            for workspace in output.workspaces.iter().flatten() {
+                paths.push(workspace.image.clone());
+            }
+        }
+        paths.sort();
+        paths.dedup();
+        paths
+    }
+}
+
+/// ipc {
+///     allow-uid 1001 1002 // other users who may send commands; the daemon's own user always can
+/// }
+#[derive(Clone, Debug, Default, knuffel::Decode, serde::Serialize, serde::Deserialize)]
+pub struct IpcConfig {
+    #[knuffel(child, unwrap(arguments), default)]
+    pub allow_uid: Vec<u32>,
 }
 
 #[derive(Clone, Default, Debug, serde::Serialize, serde::Deserialize)]
@@ -82,6 +360,10 @@ pub struct DaemonConfig {
     pub outputs: Vec<OutputConfig>,
     // lockscreen: LockscreenConfig,
     pub log_level: LogLevel,
+    pub cache: CacheConfig,
+    pub location: Option<Location>,
+    pub groups: Vec<OutputGroupConfig>,
+    pub ipc: IpcConfig,
 }
 
 pub fn get_config_dir() -> PathBuf {
@@ -108,7 +390,25 @@ fn try_load_file(path: &PathBuf) -> Option<String> {
     None
 }
 
-static mut LAST_CONFIG_FILE_CONTENTS: String = String::new();
+fn expand_schedule(schedule: &mut Option<ScheduleConfig>) {
+    for entry in schedule.iter_mut().flat_map(|s| s.entries.iter_mut()) {
+        entry.image = shellexpand::full(&entry.image).unwrap().to_string();
+    }
+}
+
+// the config watcher and `pandora reload` (on an ipc thread) both load the config, so this needs a lock
+static LAST_CONFIG_FILE_CONTENTS: Mutex<String> = Mutex::new(String::new());
+
+fn last_config_file_contents() -> MutexGuard<'static, String> {
+    // only ever holds a string, so a panic elsewhere can't have left it half-written
+    LAST_CONFIG_FILE_CONTENTS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+// for an explicit `pandora reload`: parses the file even if it hasn't changed since the last load
+pub fn reload_config() -> miette::Result<DaemonConfig> {
+    last_config_file_contents().clear();
+    load_config()
+}
 
 pub fn load_config() -> miette::Result<DaemonConfig> {
     let config_dir = get_config_dir();
@@ -118,35 +418,87 @@ pub fn load_config() -> miette::Result<DaemonConfig> {
         return Err(miette::miette!("Could not load config file from fs (if editing with vim, try backupcopy yes)"));
     }
 
-    unsafe { // hot reloading config files while also debouncing the reloads is fucking annoying :/ 
-        if LAST_CONFIG_FILE_CONTENTS == config_file_contents.clone().unwrap() {
-            return Err(miette::miette!("config file contents unchanged since last reload"));
-        }
+    // hot reloading config files while also debouncing the reloads is fucking annoying :/
+    if *last_config_file_contents() == *config_file_contents.as_ref().unwrap() {
+        return Err(miette::miette!("config file contents unchanged since last reload"));
     }
 
     let config_nodes = knuffel::parse::<Vec<ConfigNode>>(config_path.to_str().unwrap(), config_file_contents.clone().unwrap().as_str())?;
     
-    let mut config = DaemonConfig { outputs: Vec::new(), log_level: LogLevel::DEFAULT };
+    let mut config = DaemonConfig { outputs: Vec::new(), log_level: LogLevel::DEFAULT, cache: CacheConfig::default(), location: None, groups: Vec::new(), ipc: IpcConfig::default() };
     for node in config_nodes {
         match node {
             ConfigNode::Output(mut n) => {
-                n.image = shellexpand::full(&n.image).unwrap().to_string();
+                n.image.path = shellexpand::full(&n.image.path).unwrap().to_string();
+                if n.image.path.is_empty() && n.schedule.is_none() {
+                    return Err(miette::miette!("output \"{}\" needs an image or a schedule", n.name));
+                }
+                if let Some(degrees) = n.image.rotate && degrees % 90 != 0 {
+                    return Err(miette::miette!("output \"{}\": images can only be rotated by 90, 180 or 270 degrees", n.name));
+                }
+                expand_schedule(&mut n.schedule);
                 if n.workspaces.is_some() {
                     for wsc in n.workspaces.as_mut().unwrap() {
+                       // parsed only so this can say so: nothing switches images per workspace yet
+                       if wsc.schedule.is_some() {
+                           return Err(miette::miette!("output \"{}\", workspace \"{}\": schedules only work on outputs and output groups until workspaces switch images", n.name, wsc.name));
+                       }
+                       if wsc.effects != Effects::default() {
+                           return Err(miette::miette!("output \"{}\", workspace \"{}\": effects only work on outputs and output groups", n.name, wsc.name));
+                       }
                        wsc.image = shellexpand::full(&wsc.image).unwrap().to_string();
                     }
                 }
+                for layer in &mut n.layers {
+                    layer.image = shellexpand::full(&layer.image).unwrap().to_string();
+                }
                 if n.lockscreen.is_some() {
                     n.lockscreen.as_mut().unwrap().image = shellexpand::full(&n.lockscreen.as_ref().unwrap().image).unwrap().to_string();
                 }
                 config.outputs.push(n)
             },
+            ConfigNode::OutputGroup(mut g) => {
+                g.image.path = shellexpand::full(&g.image.path).unwrap().to_string();
+                if g.image.path.is_empty() && g.schedule.is_none() {
+                    return Err(miette::miette!("output-group \"{}\" needs an image or a schedule", g.name));
+                }
+                if g.outputs.is_empty() {
+                    return Err(miette::miette!("output-group \"{}\" needs some `outputs`", g.name));
+                }
+                if let Some(degrees) = g.image.rotate && degrees % 90 != 0 {
+                    return Err(miette::miette!("output-group \"{}\": images can only be rotated by 90, 180 or 270 degrees", g.name));
+                }
+                expand_schedule(&mut g.schedule);
+                config.groups.push(g);
+            },
             ConfigNode::Logging(level) => config.log_level = level,
+            ConfigNode::Cache(cache) => config.cache = cache,
+            ConfigNode::Location(location) => config.location = Some(location),
+            ConfigNode::Ipc(ipc) => config.ipc = ipc,
         }
     }
 
-    unsafe { // lol
-        LAST_CONFIG_FILE_CONTENTS = config_file_contents.unwrap();
+    for group in &config.groups {
+        for member in &group.outputs {
+            if config.outputs.iter().any(|o| o.name == *member) {
+                return Err(miette::miette!("{member} is in output-group \"{}\" and has an output stanza (or another group) of its own; pick one", group.name));
+            }
+            config.outputs.push(group.member_config(member));
+        }
+    }
+
+    if config.location.is_none() && config.schedules().any(|s| s.entries.iter().any(|e| e.time.is_solar())) {
+        return Err(miette::miette!("schedules using dawn/sunrise/noon/sunset/dusk need a `location latitude=.. longitude=..` node"));
     }
+
+    // a wallpaper pandora was built without a decoder for is a config problem, not a render-time surprise.
+    // anything else wrong with the images (missing, corrupt) is left to be logged when they're used
+    for path in config.image_paths() {
+        if let Err(DaemonError::MissingDecoder(d)) = decoders::decoder_for(Path::new(&path)) {
+            return Err(miette::Report::new(d));
+        }
+    }
+
+    *last_config_file_contents() = config_file_contents.unwrap();
     return Ok(config);
 }
\ No newline at end of file
@@ -0,0 +1,310 @@
+// one place that decides how an image file gets decoded. formats are told apart by their magic bytes (a .png that's
+// really a jpeg is fine), and formats whose decoder was left out of the build are reported as such rather than as
+// "unsupported", so it's obvious which cargo feature to turn on.
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+use image::metadata::Orientation;
+use image::{DynamicImage, ImageDecoder, ImageFormat, ImageReader};
+
+use super::color::{self, ColorSpace};
+use super::error::{DaemonError, MissingDecoder};
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Decoder {
+    Image(ImageFormat), // anything the image crate does itself
+    Jxl,
+    Heif,
+}
+
+pub struct Format {
+    pub name: &'static str,
+    pub decoder: Decoder,
+    pub feature: Option<&'static str>, // cargo feature the decoder is behind, if any
+    pub enabled: bool,
+    magic: fn(&[u8]) -> bool,
+}
+
+// longest prefix any of the checks below look at
+const SNIFF_LEN: usize = 64;
+
+// ISO-BMFF (avif, heif): "ftyp" at 4, then the major brand and a list of compatible brands
+fn ftyp_brands(b: &[u8]) -> Vec<&[u8]> {
+    if b.len() < 12 || &b[4..8] != b"ftyp" {
+        return Vec::new();
+    }
+    let box_len = (u32::from_be_bytes([b[0], b[1], b[2], b[3]]) as usize).min(b.len());
+    let mut brands = vec![&b[8..12]];
+    // skip minor_version
+    brands.extend(b.get(16..box_len).unwrap_or_default().chunks_exact(4));
+    brands
+}
+
+fn is_avif(b: &[u8]) -> bool {
+    ftyp_brands(b).iter().any(|brand| matches!(*brand, b"avif" | b"avis"))
+}
+
+fn is_heif(b: &[u8]) -> bool {
+    !is_avif(b) && ftyp_brands(b).iter().any(|brand| matches!(*brand, b"heic" | b"heix" | b"hevc" | b"hevx" | b"heim" | b"heis" | b"mif1" | b"msf1"))
+}
+
+const fn image(name: &'static str, format: ImageFormat, magic: fn(&[u8]) -> bool) -> Format {
+    Format { name, decoder: Decoder::Image(format), feature: None, enabled: true, magic }
+}
+
+// checked in order; the first match wins
+const FORMATS: &[Format] = &[
+    image("PNG", ImageFormat::Png, |b| b.starts_with(b"\x89PNG\r\n\x1a\n")),
+    image("JPEG", ImageFormat::Jpeg, |b| b.starts_with(&[0xff, 0xd8, 0xff])),
+    image("GIF", ImageFormat::Gif, |b| b.starts_with(b"GIF87a") || b.starts_with(b"GIF89a")),
+    image("WebP", ImageFormat::WebP, |b| b.starts_with(b"RIFF") && b.get(8..12) == Some(b"WEBP")),
+    image("BMP", ImageFormat::Bmp, |b| b.starts_with(b"BM")),
+    image("TIFF", ImageFormat::Tiff, |b| b.starts_with(b"II*\0") || b.starts_with(b"MM\0*")),
+    image("ICO", ImageFormat::Ico, |b| b.starts_with(&[0, 0, 1, 0])),
+    image("QOI", ImageFormat::Qoi, |b| b.starts_with(b"qoif")),
+    image("Radiance HDR", ImageFormat::Hdr, |b| b.starts_with(b"#?RADIANCE") || b.starts_with(b"#?RGBE")),
+    image("DDS", ImageFormat::Dds, |b| b.starts_with(b"DDS ")),
+    image("farbfeld", ImageFormat::Farbfeld, |b| b.starts_with(b"farbfeld")),
+    image("PNM", ImageFormat::Pnm, |b| b.len() > 2 && b[0] == b'P' && (b'1'..=b'7').contains(&b[1]) && b[2].is_ascii_whitespace()),
+    Format {
+        name: "OpenEXR",
+        decoder: Decoder::Image(ImageFormat::OpenExr),
+        feature: Some("exr"),
+        enabled: cfg!(feature = "exr"),
+        magic: |b| b.starts_with(&[0x76, 0x2f, 0x31, 0x01]),
+    },
+    Format {
+        name: "AVIF",
+        decoder: Decoder::Image(ImageFormat::Avif),
+        feature: Some("avif"),
+        enabled: cfg!(feature = "avif"),
+        magic: is_avif,
+    },
+    Format {
+        name: "HEIF",
+        decoder: Decoder::Heif,
+        feature: Some("heif"),
+        enabled: cfg!(feature = "heif"),
+        magic: is_heif,
+    },
+    Format {
+        name: "JPEG XL",
+        decoder: Decoder::Jxl,
+        feature: Some("jxl"),
+        enabled: cfg!(feature = "jxl"),
+        // bare codestream, or the container
+        magic: |b| b.starts_with(&[0xff, 0x0a]) || b.starts_with(b"\0\0\0\x0cJXL \r\n\x87\n"),
+    },
+];
+
+fn head(path: &Path) -> Result<Vec<u8>, DaemonError> {
+    let mut bytes = Vec::with_capacity(SNIFF_LEN);
+    File::open(path)?.take(SNIFF_LEN as u64).read_to_end(&mut bytes)?;
+    Ok(bytes)
+}
+
+// what `path` is, going by its contents. only TGA (which has no magic) falls back to the extension
+pub fn sniff(path: &Path) -> Result<&'static Format, DaemonError> {
+    let bytes = head(path)?;
+    if let Some(format) = FORMATS.iter().find(|f| (f.magic)(&bytes)) {
+        return Ok(format);
+    }
+    if ImageFormat::from_path(path).is_ok_and(|f| f == ImageFormat::Tga) {
+        static TGA: Format = image("TGA", ImageFormat::Tga, |_| false);
+        return Ok(&TGA);
+    }
+    Err(image::ImageError::Unsupported(image::error::UnsupportedError::from_format_and_kind(
+        image::error::ImageFormatHint::PathExtension(path.to_path_buf()),
+        image::error::UnsupportedErrorKind::Format(image::error::ImageFormatHint::Unknown),
+    )).into())
+}
+
+// like sniff, but errors if the decoder isn't compiled in
+pub fn decoder_for(path: &Path) -> Result<Decoder, DaemonError> {
+    let format = sniff(path)?;
+    if !format.enabled {
+        return Err(DaemonError::MissingDecoder(MissingDecoder {
+            path: path.to_string_lossy().to_string(),
+            format: format.name,
+            feature: format.feature.unwrap_or_default(),
+        }));
+    }
+    Ok(format.decoder)
+}
+
+// for the animation code, which only cares about formats the image crate handles
+pub fn image_format(path: &Path) -> Option<ImageFormat> {
+    match decoder_for(path) {
+        Ok(Decoder::Image(format)) => Some(format),
+        _ => None,
+    }
+}
+
+// cheap filter for directory listings, where opening every file to sniff it would be a bit much
+pub fn has_image_extension(path: &Path) -> bool {
+    let known = path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| matches!(e.to_ascii_lowercase().as_str(), "jxl" | "heic" | "heif" | "svg" | "svgz"));
+    known || ImageFormat::from_path(path).is_ok()
+}
+
+fn reader(path: &Path, format: ImageFormat) -> Result<ImageReader<BufReader<File>>, DaemonError> {
+    Ok(ImageReader::with_format(BufReader::new(File::open(path)?), format))
+}
+
+// phones mostly store photos sideways plus an exif tag saying which way is up, and tag wide gamut shots with an icc
+// profile; both get applied here so everything downstream can assume upright pixels in the returned color space
+fn decode_with(mut decoder: impl ImageDecoder, supported: &[ColorSpace]) -> Result<(DynamicImage, ColorSpace), DaemonError> {
+    let orientation = decoder.orientation().unwrap_or(Orientation::NoTransforms);
+    let icc = decoder.icc_profile().ok().flatten();
+    let mut img = DynamicImage::from_decoder(decoder)?;
+    img.apply_orientation(orientation);
+    Ok(color::prepare(img, icc.as_deref(), supported))
+}
+
+// as displayed, i.e. swapped for images stored on their side
+fn oriented_dimensions(mut decoder: impl ImageDecoder) -> (u32, u32) {
+    let (width, height) = decoder.dimensions();
+    match decoder.orientation().unwrap_or(Orientation::NoTransforms) {
+        Orientation::Rotate90 | Orientation::Rotate270 | Orientation::Rotate90FlipH | Orientation::Rotate270FlipH => (height, width),
+        _ => (width, height),
+    }
+}
+
+// `supported`: the color spaces surfaces can be tagged with. images in any other space come back as sRGB
+pub fn decode(path: &Path, supported: &[ColorSpace]) -> Result<(DynamicImage, ColorSpace), DaemonError> {
+    match decoder_for(path)? {
+        Decoder::Image(format) => decode_with(reader(path, format)?.into_decoder()?, supported),
+        Decoder::Jxl => decode_jxl(path, supported),
+        Decoder::Heif => decode_heif(path, supported),
+    }
+}
+
+pub fn dimensions(path: &Path) -> Result<(u32, u32), DaemonError> {
+    match decoder_for(path)? {
+        Decoder::Image(format) => Ok(oriented_dimensions(reader(path, format)?.into_decoder()?)),
+        Decoder::Jxl => jxl_dimensions(path),
+        Decoder::Heif => heif_dimensions(path),
+    }
+}
+
+// what decode would hand back, from the headers alone
+pub fn color_space(path: &Path, supported: &[ColorSpace]) -> Result<ColorSpace, DaemonError> {
+    let icc = match decoder_for(path)? {
+        Decoder::Image(format) => reader(path, format)?.into_decoder()?.icc_profile().ok().flatten(),
+        Decoder::Jxl => jxl_icc(path)?,
+        Decoder::Heif => heif_icc(path)?,
+    };
+    Ok(color::target(icc.as_deref(), supported))
+}
+
+#[cfg(feature = "jxl")]
+fn decode_jxl(path: &Path, supported: &[ColorSpace]) -> Result<(DynamicImage, ColorSpace), DaemonError> {
+    // jxl-oxide already renders upright, so decode_with only ends up doing the color part
+    decode_with(jxl_oxide::integration::JxlDecoder::new(BufReader::new(File::open(path)?))?, supported)
+}
+
+#[cfg(feature = "jxl")]
+fn jxl_icc(path: &Path) -> Result<Option<Vec<u8>>, DaemonError> {
+    Ok(jxl_oxide::integration::JxlDecoder::new(BufReader::new(File::open(path)?))?.icc_profile()?)
+}
+
+#[cfg(feature = "jxl")]
+fn jxl_dimensions(path: &Path) -> Result<(u32, u32), DaemonError> {
+    Ok(jxl_oxide::integration::JxlDecoder::new(BufReader::new(File::open(path)?))?.dimensions())
+}
+
+#[cfg(feature = "heif")]
+fn heif_handle(path: &Path) -> Result<(libheif_rs::HeifContext<'static>, libheif_rs::ImageHandle), DaemonError> {
+    let heif_err = |e: libheif_rs::HeifError| DaemonError::IoError(std::io::Error::other(e.to_string()));
+    let ctx = libheif_rs::HeifContext::read_from_file(&path.to_string_lossy()).map_err(heif_err)?;
+    let handle = ctx.primary_image_handle().map_err(heif_err)?;
+    Ok((ctx, handle))
+}
+
+#[cfg(feature = "heif")]
+fn decode_heif(path: &Path, supported: &[ColorSpace]) -> Result<(DynamicImage, ColorSpace), DaemonError> {
+    use libheif_rs::{ColorSpace, LibHeif, RgbChroma};
+    let (_ctx, handle) = heif_handle(path)?;
+    let decoded = LibHeif::new()
+        .decode(&handle, ColorSpace::Rgb(RgbChroma::Rgba), None)
+        .map_err(|e| DaemonError::IoError(std::io::Error::other(e.to_string())))?;
+    let plane = decoded.planes().interleaved.ok_or(DaemonError::LogicalError)?;
+    // rows can be padded out past width * 4
+    let mut pixels = Vec::with_capacity(plane.width as usize * plane.height as usize * 4);
+    for row in plane.data.chunks(plane.stride).take(plane.height as usize) {
+        pixels.extend_from_slice(&row[..plane.width as usize * 4]);
+    }
+    let img = image::RgbaImage::from_raw(plane.width, plane.height, pixels).ok_or(DaemonError::LogicalError)?;
+    // libheif has already applied the rotation/mirroring boxes, leaving only the color profile to us
+    let icc = handle.color_profile_raw().map(|p| p.data);
+    Ok(color::prepare(DynamicImage::ImageRgba8(img), icc.as_deref(), supported))
+}
+
+#[cfg(feature = "heif")]
+fn heif_icc(path: &Path) -> Result<Option<Vec<u8>>, DaemonError> {
+    let (_ctx, handle) = heif_handle(path)?;
+    Ok(handle.color_profile_raw().map(|p| p.data))
+}
+
+#[cfg(feature = "heif")]
+fn heif_dimensions(path: &Path) -> Result<(u32, u32), DaemonError> {
+    let (_ctx, handle) = heif_handle(path)?;
+    Ok((handle.width(), handle.height()))
+}
+
+// decoder_for never hands these out when the feature is off
+#[cfg(not(feature = "jxl"))]
+fn decode_jxl(_: &Path, _: &[ColorSpace]) -> Result<(DynamicImage, ColorSpace), DaemonError> {
+    Err(DaemonError::LogicalError)
+}
+
+#[cfg(not(feature = "jxl"))]
+fn jxl_icc(_: &Path) -> Result<Option<Vec<u8>>, DaemonError> {
+    Err(DaemonError::LogicalError)
+}
+
+#[cfg(not(feature = "jxl"))]
+fn jxl_dimensions(_: &Path) -> Result<(u32, u32), DaemonError> {
+    Err(DaemonError::LogicalError)
+}
+
+#[cfg(not(feature = "heif"))]
+fn decode_heif(_: &Path, _: &[ColorSpace]) -> Result<(DynamicImage, ColorSpace), DaemonError> {
+    Err(DaemonError::LogicalError)
+}
+
+#[cfg(not(feature = "heif"))]
+fn heif_icc(_: &Path) -> Result<Option<Vec<u8>>, DaemonError> {
+    Err(DaemonError::LogicalError)
+}
+
+#[cfg(not(feature = "heif"))]
+fn heif_dimensions(_: &Path) -> Result<(u32, u32), DaemonError> {
+    Err(DaemonError::LogicalError)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sniffs_by_contents_not_extension() {
+        let dir = tempfile::tempdir().unwrap();
+        let liar = dir.path().join("actually-a-png.jpg");
+        image::RgbaImage::new(2, 2).save_with_format(&liar, ImageFormat::Png).unwrap();
+        assert_eq!(sniff(&liar).unwrap().name, "PNG");
+        assert_eq!(dimensions(&liar).unwrap(), (2, 2));
+
+        let avif = dir.path().join("photo.avif");
+        std::fs::write(&avif, b"\0\0\0\x20ftypavif\0\0\0\0avifmif1miafMA1B").unwrap();
+        let heic = dir.path().join("photo.heic");
+        std::fs::write(&heic, b"\0\0\0\x18ftypheic\0\0\0\0mif1heic").unwrap();
+        assert_eq!(sniff(&avif).unwrap().name, "AVIF");
+        assert_eq!(sniff(&heic).unwrap().name, "HEIF");
+        if !cfg!(feature = "avif") {
+            assert!(matches!(decoder_for(&avif), Err(DaemonError::MissingDecoder(d)) if d.feature == "avif"));
+        }
+    }
+}
@@ -0,0 +1,136 @@
+// post-processing applied to an image once it's been scaled for an output: blur, brightness, saturation, a tint and
+// opacity.
+// done at scale time so it's paid for once per (image, size, effects) and then lives in the buffer/disk caches like
+// any other scaled image; the source file is never touched.
+use std::fmt;
+use std::str::FromStr;
+
+use image::RgbaImage;
+use knuffel::ast::{Literal, TypeName};
+use knuffel::decode::{Context, Kind};
+use knuffel::errors::DecodeError;
+use knuffel::span::Spanned;
+use knuffel::traits::ErrorSpan;
+
+use super::config::Effects;
+
+// a number that can be written either way in the config (`blur 8` and `blur 8.0`); plain f32s only take the latter
+#[derive(Copy, Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Number(pub f32);
+
+impl<S: ErrorSpan> knuffel::DecodeScalar<S> for Number {
+    fn type_check(_: &Option<Spanned<TypeName, S>>, _: &mut Context<S>) {}
+
+    fn raw_decode(val: &Spanned<Literal, S>, _: &mut Context<S>) -> Result<Number, DecodeError<S>> {
+        match &**val {
+            Literal::Int(i) => i64::try_from(i).map(|v| Number(v as f32)).map_err(|e| DecodeError::conversion(val, e)),
+            Literal::Decimal(d) => f32::try_from(d).map(Number).map_err(|e| DecodeError::conversion(val, e)),
+            _ => Err(DecodeError::scalar_kind(Kind::Decimal, val)),
+        }
+    }
+}
+
+// "#rrggbb"
+#[derive(Copy, Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Color(pub [u8; 3]);
+
+impl FromStr for Color {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Color, String> {
+        let hex = s.strip_prefix('#').unwrap_or(s);
+        let channel = |i: usize| hex.get(i..i + 2).and_then(|c| u8::from_str_radix(c, 16).ok());
+        match (hex.len(), channel(0), channel(2), channel(4)) {
+            (6, Some(r), Some(g), Some(b)) => Ok(Color([r, g, b])),
+            _ => Err(format!("expected a color like \"#223344\", got \"{s}\"")),
+        }
+    }
+}
+
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let [r, g, b] = self.0;
+        write!(f, "#{r:02x}{g:02x}{b:02x}")
+    }
+}
+
+impl Effects {
+    pub fn is_empty(&self) -> bool {
+        self.blur.is_none() && self.brightness.is_none() && self.saturate.is_none() && self.tint.is_none()
+            && self.opacity.is_none()
+    }
+
+    // stable short description, for cache keys. empty when there's nothing to do, so plain images keep their keys
+    pub fn cache_id(&self) -> String {
+        let mut id = String::new();
+        if let Some(Number(sigma)) = self.blur {
+            id += &format!("b{sigma}");
+        }
+        if let Some(Number(factor)) = self.brightness {
+            id += &format!("l{factor}");
+        }
+        if let Some(Number(factor)) = self.saturate {
+            id += &format!("s{factor}");
+        }
+        if let Some(tint) = &self.tint {
+            id += &format!("t{}@{}", tint.color, tint.amount.0);
+        }
+        if let Some(Number(opacity)) = self.opacity {
+            id += &format!("o{opacity}");
+        }
+        id
+    }
+
+    // blur first (it's what everything else should see), then the per-pixel adjustments in one pass. alpha is only
+    // touched by opacity, which scales it
+    pub fn apply(&self, img: RgbaImage) -> RgbaImage {
+        let mut img = match self.blur {
+            Some(Number(sigma)) if sigma > 0.0 => image::imageops::fast_blur(&img, sigma),
+            _ => img,
+        };
+        if self.brightness.is_none() && self.saturate.is_none() && self.tint.is_none() && self.opacity.is_none() {
+            return img;
+        }
+        let brightness = self.brightness.map_or(1.0, |n| n.0.max(0.0));
+        let saturate = self.saturate.map_or(1.0, |n| n.0.max(0.0));
+        let (tint, amount) = match &self.tint {
+            Some(t) => (t.color.0.map(f32::from), t.amount.0.clamp(0.0, 1.0)),
+            None => ([0.0; 3], 0.0),
+        };
+        let opacity = self.opacity.map_or(1.0, |n| n.0.clamp(0.0, 1.0));
+        for pixel in img.pixels_mut() {
+            let [r, g, b, _] = pixel.0.map(|c| c as f32 * brightness);
+            pixel.0[3] = (pixel.0[3] as f32 * opacity).round() as u8;
+            let luma = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+            for (i, c) in [r, g, b].into_iter().enumerate() {
+                let c = luma + (c - luma) * saturate;
+                let c = c * (1.0 - amount) + tint[i] * amount;
+                pixel.0[i] = c.round().clamp(0.0, 255.0) as u8;
+            }
+        }
+        img
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::config::Tint;
+
+    #[test]
+    fn adjusts_pixels_and_keys() {
+        let effects = Effects {
+            blur: None,
+            brightness: Some(Number(0.5)),
+            saturate: Some(Number(0.0)),
+            tint: Some(Tint { color: "#0000ff".parse().unwrap(), amount: Number(0.5) }),
+            opacity: None,
+        };
+        let img = RgbaImage::from_pixel(1, 1, image::Rgba([200, 100, 0, 128]));
+        // halved to (100, 50, 0), greyed out to its luma (57), then half way to blue
+        assert_eq!(effects.apply(img).get_pixel(0, 0).0, [29, 29, 156, 128]);
+        assert_eq!(effects.cache_id(), "l0.5s0t#0000ff@0.5");
+        assert_eq!(Effects::default().cache_id(), "");
+        assert!("#12345".parse::<Color>().is_err());
+    }
+}
@@ -1,25 +1,86 @@
+use pandora_ipc::sockets::{ErrorCode, Response};
+
 #[derive(Debug)]
 pub struct CommandError {
     pub response: String,
+    pub code: ErrorCode, // what the ipc client gets told
 }
 
 impl CommandError {
     pub fn new(s: &str) -> DaemonError {
-        return DaemonError::CommandError(CommandError{
-            response: s.to_string()
+        CommandError::coded(ErrorCode::InvalidArgument, s)
+    }
+
+    pub fn coded(code: ErrorCode, s: &str) -> DaemonError {
+        DaemonError::CommandError(CommandError{
+            response: s.to_string(),
+            code,
         })
     }
 }
 
+// an image in a format whose decoder is behind a cargo feature that wasn't turned on
+#[derive(Clone, Debug, miette::Diagnostic)]
+#[diagnostic(code(pandora::missing_decoder), help("rebuild pandora with `--features {feature}`"))]
+pub struct MissingDecoder {
+    pub path: String,
+    pub format: &'static str,
+    pub feature: &'static str,
+}
+
+impl std::fmt::Display for MissingDecoder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} is a {} image, but this build of pandora can't decode {}", self.path, self.format, self.format)
+    }
+}
+
+impl std::error::Error for MissingDecoder {}
+
 #[derive(Debug)]
 pub enum DaemonError {
     IoError(std::io::Error),
     ImageError(image::ImageError),
+    SvgError(resvg::usvg::Error),
+    MissingDecoder(MissingDecoder),
     CommandError(CommandError),
     LogicalError, // general encapsulation of "invalid operation, maybe due to bad config"
     PoisonError, // typedef annoying and doesn't really add much. "a mutex got fucked" is all that really matters
 }
 
+impl std::fmt::Display for DaemonError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DaemonError::IoError(e) => write!(f, "{e}"),
+            DaemonError::ImageError(e) => write!(f, "{e}"),
+            DaemonError::SvgError(e) => write!(f, "{e}"),
+            DaemonError::MissingDecoder(d) => write!(f, "{d} (rebuild with `--features {}`)", d.feature),
+            DaemonError::CommandError(c) => write!(f, "{}", c.response),
+            DaemonError::LogicalError => write!(f, "invalid operation"),
+            DaemonError::PoisonError => write!(f, "a lock was poisoned"),
+        }
+    }
+}
+
+impl DaemonError {
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            DaemonError::IoError(e) if e.kind() == std::io::ErrorKind::NotFound => ErrorCode::NotFound,
+            DaemonError::IoError(_) => ErrorCode::Internal,
+            DaemonError::ImageError(image::ImageError::IoError(e)) if e.kind() == std::io::ErrorKind::NotFound => ErrorCode::NotFound,
+            DaemonError::ImageError(_) | DaemonError::SvgError(_) => ErrorCode::Image,
+            DaemonError::MissingDecoder(_) => ErrorCode::MissingDecoder,
+            DaemonError::CommandError(c) => c.code,
+            DaemonError::LogicalError => ErrorCode::InvalidArgument,
+            DaemonError::PoisonError => ErrorCode::Internal,
+        }
+    }
+
+    // for ipc clients; `context` goes in front of the error's own description
+    pub fn to_response(&self, context: &str) -> Response {
+        Response::err(self.code(), format!("{context}: {self}"))
+    }
+}
+
 impl From<std::io::Error> for DaemonError {
     fn from(err: std::io::Error) -> DaemonError {
         DaemonError::IoError(err)
@@ -32,6 +93,12 @@ impl From<image::ImageError> for DaemonError {
     }
 }
 
+impl From<resvg::usvg::Error> for DaemonError {
+    fn from(err: resvg::usvg::Error) -> DaemonError {
+        DaemonError::SvgError(err)
+    }
+}
+
 impl<T> From<std::sync::PoisonError<T>> for DaemonError {
     fn from(_: std::sync::PoisonError<T>) -> DaemonError {
         DaemonError::PoisonError
@@ -0,0 +1,65 @@
+// things that happened (pandora_ipc::events::Event), for whoever's connected with an EventStream request (bars,
+// scripts, `pandora events`).
+// every subscriber gets its own bounded queue: the daemon never waits on one, and a subscriber that falls a whole
+// queue behind is assumed to be gone and gets cut off rather than left to eat memory
+use pandora_ipc::events::Event;
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender, TrySendError};
+
+const BACKLOG: usize = 256;
+
+#[derive(Default)]
+pub struct Subscribers {
+    senders: Vec<SyncSender<Event>>,
+}
+
+impl Subscribers {
+    pub fn subscribe(&mut self) -> Receiver<Event> {
+        let (send, recv) = sync_channel(BACKLOG);
+        self.senders.push(send);
+        recv
+    }
+
+    // returns how many were dropped for being too slow
+    pub fn send(&mut self, event: &Event) -> usize {
+        let mut slow = 0;
+        self.senders.retain(|s| match s.try_send(event.clone()) {
+            Ok(_) => true,
+            Err(TrySendError::Full(_)) => {
+                slow += 1;
+                false
+            },
+            Err(TrySendError::Disconnected(_)) => false,
+        });
+        slow
+    }
+
+    pub fn len(&self) -> usize {
+        self.senders.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.senders.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slow_and_gone_subscribers_are_dropped() {
+        let mut subscribers = Subscribers::default();
+        let keen = subscribers.subscribe();
+        let _slow = subscribers.subscribe();
+        drop(subscribers.subscribe());
+        for _ in 0..BACKLOG {
+            assert_eq!(subscribers.send(&Event::Locked), 0);
+            assert_eq!(keen.try_recv(), Ok(Event::Locked));
+        }
+        // the dropped one went on the first send; the one nobody reads from goes once its queue is full
+        assert_eq!(subscribers.len(), 2);
+        assert_eq!(subscribers.send(&Event::Locked), 1);
+        assert_eq!(subscribers.len(), 1);
+        assert_eq!(keen.try_recv(), Ok(Event::Locked));
+    }
+}
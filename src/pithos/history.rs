@@ -0,0 +1,73 @@
+// what an output has shown, newest last, for `pandora history` and `pandora undo`.
+// undo is a stack: it drops the newest change and goes back to the one before, so doing it again goes further back
+use pandora_ipc::status::HistoryEntry;
+
+use std::collections::VecDeque;
+
+const HISTORY_LEN: usize = 32;
+
+#[derive(Default)]
+pub struct History {
+    entries: VecDeque<HistoryEntry>,
+}
+
+impl History {
+    // false if it's just a redraw of what's already there (a mode change, a re-cut group slice..)
+    pub fn push(&mut self, entry: HistoryEntry) -> bool {
+        if self.entries.back().is_some_and(|e| e.image == entry.image && e.mode == entry.mode) {
+            return false;
+        }
+        self.entries.push_back(entry);
+        if self.entries.len() > HISTORY_LEN {
+            self.entries.pop_front();
+        }
+        true
+    }
+
+    // what undo would go back to
+    pub fn previous(&self) -> Option<&HistoryEntry> {
+        self.entries.len().checked_sub(2).and_then(|i| self.entries.get(i))
+    }
+
+    // forgets the newest change; returns what's current again
+    pub fn undo(&mut self) -> Option<HistoryEntry> {
+        self.previous()?;
+        self.entries.pop_back();
+        self.entries.back().cloned()
+    }
+
+    pub fn newest_first(&self) -> Vec<HistoryEntry> {
+        self.entries.iter().rev().cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pandora_ipc::commands::RenderMode;
+    use pandora_ipc::status::ChangeCause;
+
+    fn entry(image: &str, mode: RenderMode) -> HistoryEntry {
+        HistoryEntry { image: image.to_string(), mode, at: 0, cause: ChangeCause::Set }
+    }
+
+    #[test]
+    fn undo_walks_back_through_real_changes() {
+        let mut history = History::default();
+        assert!(history.push(entry("/a.png", RenderMode::Static)));
+        assert!(!history.push(entry("/a.png", RenderMode::Static)));
+        assert!(history.push(entry("/a.png", RenderMode::ScrollVertical)));
+        assert!(history.push(entry("/b.png", RenderMode::Static)));
+        assert_eq!(history.undo(), Some(entry("/a.png", RenderMode::ScrollVertical)));
+        assert_eq!(history.undo(), Some(entry("/a.png", RenderMode::Static)));
+        assert_eq!(history.undo(), None);
+        assert_eq!(history.newest_first().len(), 1);
+
+        for i in 0..HISTORY_LEN * 2 {
+            history.push(entry(&format!("/{i}.png"), RenderMode::Static));
+        }
+        let entries = history.newest_first();
+        assert_eq!(entries.len(), HISTORY_LEN);
+        assert_eq!(entries[0].image, format!("/{}.png", HISTORY_LEN * 2 - 1));
+    }
+}
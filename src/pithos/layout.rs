@@ -0,0 +1,81 @@
+// output groups: one image spread over several outputs, cut up by where they sit in the compositor's logical layout.
+// every member renders like any other output, just through a transform whose `region` is its own slice of the image
+use super::commands::RenderMode;
+use super::config::Crop;
+
+// an output's position and size in the compositor's global space. lives in pandora_ipc since `pandora status` reports it
+pub use pandora_ipc::status::LogicalRect;
+
+// the members laid out on one canvas: `bezel` logical pixels go in every gap between neighbouring outputs (the image
+// carries on behind the frames), and everything's shifted so the canvas starts at 0,0. returns the canvas size too
+pub fn group_canvas(members: &[LogicalRect], bezel: u32) -> (Vec<LogicalRect>, (u32, u32)) {
+    let spaced: Vec<LogicalRect> = members.iter().map(|r| {
+        let left = members.iter().filter(|m| m.x + m.width as i32 <= r.x).count() as i32;
+        let above = members.iter().filter(|m| m.y + m.height as i32 <= r.y).count() as i32;
+        LogicalRect { x: r.x + left * bezel as i32, y: r.y + above * bezel as i32, ..*r }
+    }).collect();
+    let min_x = spaced.iter().map(|r| r.x).min().unwrap_or(0);
+    let min_y = spaced.iter().map(|r| r.y).min().unwrap_or(0);
+    let max_x = spaced.iter().map(|r| r.x + r.width as i32).max().unwrap_or(0);
+    let max_y = spaced.iter().map(|r| r.y + r.height as i32).max().unwrap_or(0);
+    let shifted = spaced.into_iter().map(|r| LogicalRect { x: r.x - min_x, y: r.y - min_y, ..r }).collect();
+    (shifted, ((max_x - min_x) as u32, (max_y - min_y) as u32))
+}
+
+// the part of an image_width x image_height image `output` shows. static groups cover the whole canvas (centered,
+// like a single output would be); scrolling ones only fit across it and hand each member a full-length strip, which
+// the niri agent then scrolls in step. None if `output` isn't laid out yet
+pub fn group_region(members: &[(String, LogicalRect)], output: &str, bezel: u32, image: (u32, u32), mode: RenderMode) -> Option<Crop> {
+    let index = members.iter().position(|(name, _)| name == output)?;
+    let rects: Vec<LogicalRect> = members.iter().map(|(_, r)| *r).collect();
+    let (canvas, (canvas_width, canvas_height)) = group_canvas(&rects, bezel);
+    let rect = canvas[index];
+    let (image_width, image_height) = (image.0 as f64, image.1 as f64);
+    // logical pixels per image pixel
+    let scale = match mode {
+        RenderMode::Static => f64::max(canvas_width as f64 / image_width, canvas_height as f64 / image_height),
+        RenderMode::ScrollVertical => canvas_width as f64 / image_width,
+        RenderMode::ScrollLateral => canvas_height as f64 / image_height,
+    };
+    let offset_x = (image_width * scale - canvas_width as f64).max(0.0) / 2.0;
+    let offset_y = (image_height * scale - canvas_height as f64).max(0.0) / 2.0;
+    let to_image = |v: f64| (v / scale).round().max(0.0) as u32;
+    let (x, width) = match mode {
+        RenderMode::ScrollLateral => (0, image.0),
+        _ => (to_image(rect.x as f64 + offset_x), to_image(rect.width as f64).max(1)),
+    };
+    let (y, height) = match mode {
+        RenderMode::ScrollVertical => (0, image.1),
+        _ => (to_image(rect.y as f64 + offset_y), to_image(rect.height as f64).max(1)),
+    };
+    Some(Crop { x, y, width, height })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(x: i32, y: i32, width: u32, height: u32) -> LogicalRect {
+        LogicalRect { x, y, width, height }
+    }
+
+    #[test]
+    fn cuts_a_row_of_outputs_with_bezels() {
+        let members = vec![
+            ("DP-1".to_string(), rect(-1920, 0, 1920, 1080)),
+            ("DP-2".to_string(), rect(0, 0, 1920, 1080)),
+            ("DP-3".to_string(), rect(1920, 0, 1920, 1080)),
+        ];
+        let (canvas, size) = group_canvas(&members.iter().map(|m| m.1).collect::<Vec<_>>(), 40);
+        assert_eq!(size, (5840, 1080));
+        assert_eq!(canvas[2], rect(3920, 0, 1920, 1080));
+
+        // an image twice the canvas' size: everything in image pixels is doubled
+        let middle = group_region(&members, "DP-2", 40, (11680, 2160), RenderMode::Static).unwrap();
+        assert_eq!(middle, Crop { x: 3920, y: 0, width: 3840, height: 2160 });
+        // scrolling vertically keeps the whole height
+        let right = group_region(&members, "DP-3", 40, (11680, 8000), RenderMode::ScrollVertical).unwrap();
+        assert_eq!(right, Crop { x: 7840, y: 0, width: 3840, height: 8000 });
+        assert!(group_region(&members, "HDMI-A-1", 40, (11680, 2160), RenderMode::Static).is_none());
+    }
+}
@@ -1,13 +1,26 @@
 use std::{fs::File, io::Write};
 use image::RgbaImage;
 
+use super::commands::RenderMode;
+
 pub fn img_into_buffer(img: &RgbaImage, f: &File) {
     let mut buf = std::io::BufWriter::new(f);
     // this could potentially be SIMD'd or otherwise accelerated, I think
     // definitely a bottleneck in the load
     for pixel in img.pixels() {
         let (r, g, b, a) = (pixel.0[0], pixel.0[1],pixel.0[2],pixel.0[3]);
-        buf.write_all(&[b as u8, g as u8, r as u8, a as u8]).unwrap();
+        // wl_shm wants premultiplied alpha. only matters for things with see-through bits, i.e. layers
+        let premultiply = |c: u8| ((c as u16 * a as u16 + 127) / 255) as u8;
+        buf.write_all(&[premultiply(b), premultiply(g), premultiply(r), a]).unwrap();
+    }
+}
+
+// what an image gets scaled to fit for each mode: both sides for static, the side that doesn't scroll otherwise
+pub fn mode_scale_to(mode: RenderMode, output_width: u32, output_height: u32) -> (Option<u32>, Option<u32>) {
+    match mode {
+        RenderMode::Static => (Some(output_width), Some(output_height)),
+        RenderMode::ScrollVertical => (Some(output_width), None),
+        RenderMode::ScrollLateral => (None, Some(output_height)),
     }
 }
 
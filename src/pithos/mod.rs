@@ -1,8 +1,21 @@
 // the general misc parts of the library crate:
 // config, inter-thread messaging structs, errors, socketry, and the most truly miscelleneous of the odds and ends
+pub mod animation;
 pub mod anims;
+pub mod cache;
+pub mod color;
 pub mod commands;
 pub mod config;
+pub mod decoders;
+pub mod effects;
 pub mod error;
+pub mod events;
+pub mod history;
+pub mod layout;
 pub mod misc;
-pub mod sockets;
\ No newline at end of file
+pub mod packages;
+pub mod schedule;
+pub mod slideshow;
+pub mod state;
+pub mod svg;
+pub mod transform;
\ No newline at end of file
@@ -0,0 +1,192 @@
+// wallpapers packaged up for other desktops:
+// - GNOME timed backgrounds: an xml file of <static> images and <transition>s with durations, cycling from a start time
+// - KDE wallpaper packages: a directory with metadata.json and the same image at several resolutions
+// both get flattened into what pandora already understands: a KDE package is just one file once we know the output
+// size, and a GNOME background becomes a schedule.
+use std::fs;
+use std::path::Path;
+
+use chrono::{NaiveDateTime, Timelike};
+
+use super::config::ScheduleEntry;
+use super::schedule::ScheduleTime;
+
+const DAY_SECS: u64 = 24 * 60 * 60;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Variant {
+    pub path: String,
+    pub width: u32,
+    pub height: u32,
+}
+
+// the smallest variant that covers the output, so we only ever scale down; the biggest one if nothing does
+pub fn closest_variant(variants: &[Variant], size: Option<(u32, u32)>) -> Option<&Variant> {
+    let largest = variants.iter().max_by_key(|v| v.width as u64 * v.height as u64);
+    let (width, height) = match size {
+        Some(s) => s,
+        None => return largest,
+    };
+    variants.iter()
+        .filter(|v| v.width >= width && v.height >= height)
+        .min_by_key(|v| v.width as u64 * v.height as u64)
+        .or(largest)
+}
+
+pub fn is_kde_package(path: &Path) -> bool {
+    path.join("metadata.json").is_file() && path.join("contents").is_dir()
+}
+
+pub fn is_gnome_background(path: &Path) -> bool {
+    path.extension().is_some_and(|e| e.eq_ignore_ascii_case("xml")) && path.is_file()
+}
+
+fn parse_size(name: &str) -> Option<(u32, u32)> {
+    let (w, h) = name.split_once('x')?;
+    Some((w.parse().ok()?, h.parse().ok()?))
+}
+
+// contents/images/1920x1080.png and friends. images_dark/ is ignored until we grow a notion of dark mode
+pub fn kde_variants(package: &Path) -> Vec<Variant> {
+    let mut variants = Vec::new();
+    let entries = match fs::read_dir(package.join("contents").join("images")) {
+        Ok(entries) => entries,
+        Err(_) => return variants,
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let size = path.file_stem().and_then(|s| s.to_str()).and_then(parse_size);
+        if let Some((width, height)) = size {
+            variants.push(Variant { path: path.to_string_lossy().to_string(), width, height });
+        }
+    }
+    variants.sort_by_key(|v| (v.width, v.height));
+    variants
+}
+
+// <file>path</file>, or <file><size width=".." height="..">path</size>...</file>
+fn gnome_file(node: roxmltree::Node, base: &Path, size: Option<(u32, u32)>) -> Option<String> {
+    let file = node.children().find(|c| c.has_tag_name("file"))?;
+    let resolve = |p: &str| base.join(p.trim()).to_string_lossy().to_string();
+    let variants: Vec<Variant> = file.children()
+        .filter(|c| c.has_tag_name("size"))
+        .filter_map(|c| Some(Variant {
+            path: resolve(c.text()?),
+            width: c.attribute("width")?.parse().ok()?,
+            height: c.attribute("height")?.parse().ok()?,
+        }))
+        .collect();
+    if !variants.is_empty() {
+        return closest_variant(&variants, size).map(|v| v.path.clone());
+    }
+    file.text().map(resolve)
+}
+
+fn child_text<'a>(node: roxmltree::Node<'a, 'a>, tag: &str) -> Option<&'a str> {
+    node.children().find(|c| c.has_tag_name(tag))?.text().map(str::trim)
+}
+
+fn gnome_start_time(root: roxmltree::Node) -> Option<NaiveDateTime> {
+    let start = root.children().find(|c| c.has_tag_name("starttime"))?;
+    let field = |tag| child_text(start, tag).and_then(|t| t.parse::<u32>().ok()).unwrap_or(0);
+    chrono::NaiveDate::from_ymd_opt(field("year") as i32, field("month"), field("day"))?
+        .and_hms_opt(field("hour"), field("minute"), field("second"))
+}
+
+// pandora doesn't crossfade (yet), so a <transition> is just time before the next image takes over;
+// we switch at its start, which is where the new image starts showing through in GNOME.
+// cycles are assumed to repeat from the start time's time of day; shorter ones get repeated to fill the day
+pub fn gnome_schedule(xml: &Path, size: Option<(u32, u32)>) -> Result<Vec<ScheduleEntry>, String> {
+    let contents = fs::read_to_string(xml).map_err(|e| format!("could not read {}: {e}", xml.display()))?;
+    let doc = roxmltree::Document::parse(&contents).map_err(|e| format!("could not parse {}: {e}", xml.display()))?;
+    let root = doc.root_element();
+    if !root.has_tag_name("background") {
+        return Err(format!("{} is not a GNOME <background> file", xml.display()));
+    }
+    let base = xml.parent().map(Path::to_path_buf).unwrap_or_default();
+    let start = gnome_start_time(root).map(|t| t.num_seconds_from_midnight() as u64).unwrap_or(0);
+
+    // (offset into the cycle, image)
+    let mut slides: Vec<(u64, String)> = Vec::new();
+    let mut offset = 0u64;
+    for node in root.children().filter(|n| n.is_element()) {
+        let duration = child_text(node, "duration").and_then(|d| d.parse::<f64>().ok()).unwrap_or(0.0).max(0.0) as u64;
+        if node.has_tag_name("static") {
+            if let Some(image) = gnome_file(node, &base, size) {
+                slides.push((offset, image));
+            }
+        } else if node.has_tag_name("transition")
+            && let Some(to) = child_text(node, "to") {
+            slides.push((offset, base.join(to).to_string_lossy().to_string()));
+        } else {
+            continue;
+        }
+        offset += duration;
+    }
+    let cycle = offset;
+    if slides.is_empty() || cycle == 0 {
+        return Err(format!("{} has no timed images", xml.display()));
+    }
+
+    // (seconds since midnight, image) for one day's worth of cycles
+    let mut times: Vec<(u64, String)> = Vec::new();
+    let mut cycle_start = 0;
+    while cycle_start < DAY_SECS {
+        for (at, image) in &slides {
+            // schedules go by the minute
+            let secs = (start + cycle_start + at + 30) / 60 * 60 % DAY_SECS;
+            times.push((secs, image.clone()));
+        }
+        cycle_start += cycle;
+    }
+    // later slides win when rounding lands two on the same minute
+    times.reverse();
+    times.sort_by_key(|(secs, _)| *secs);
+    times.dedup_by_key(|(secs, _)| *secs);
+    // a static followed by a transition into the same image is all one entry to us
+    let mut entries: Vec<ScheduleEntry> = Vec::new();
+    for (i, (secs, image)) in times.iter().enumerate() {
+        let prev = if i == 0 { times.last() } else { times.get(i - 1) };
+        if times.len() > 1 && prev.is_some_and(|(_, p)| p == image) {
+            continue;
+        }
+        entries.push(ScheduleEntry {
+            time: ScheduleTime::Clock { hour: (secs / 3600) as u32, minute: (secs % 3600 / 60) as u32 },
+            image: image.clone(),
+            offset: None,
+        });
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn picks_smallest_variant_covering_output() {
+        let v = |width, height| Variant { path: format!("{width}x{height}"), width, height };
+        let variants = vec![v(1280, 800), v(1920, 1080), v(2560, 1440), v(3840, 2160)];
+        assert_eq!(closest_variant(&variants, Some((1920, 1200))).unwrap().path, "2560x1440");
+        assert_eq!(closest_variant(&variants, Some((1920, 1080))).unwrap().path, "1920x1080");
+        assert_eq!(closest_variant(&variants, Some((5120, 2880))).unwrap().path, "3840x2160");
+    }
+
+    #[test]
+    fn flattens_gnome_background_into_schedule() {
+        let dir = tempfile::tempdir().unwrap();
+        let xml = dir.path().join("timed.xml");
+        fs::write(&xml, r#"<background>
+            <starttime><year>2011</year><month>11</month><day>24</day><hour>7</hour><minute>00</minute><second>00</second></starttime>
+            <static><duration>43195.0</duration><file>day.png</file></static>
+            <transition type="overlay"><duration>5.0</duration><from>day.png</from><to>night.png</to></transition>
+            <static><duration>43195.0</duration><file>night.png</file></static>
+            <transition type="overlay"><duration>5.0</duration><from>night.png</from><to>day.png</to></transition>
+        </background>"#).unwrap();
+        let entries = gnome_schedule(&xml, None).unwrap();
+        let times: Vec<String> = entries.iter().map(|e| e.time.to_string()).collect();
+        assert_eq!(times, vec!["07:00", "19:00"]);
+        assert!(entries[0].image.ends_with("day.png"));
+        assert!(entries[1].image.ends_with("night.png"));
+    }
+}
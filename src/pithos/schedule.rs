@@ -0,0 +1,187 @@
+// time-of-day wallpapers. entries are pinned to a wall clock time or to a solar event, which we work out ourselves
+// from the configured location (NOAA's simplified sunrise equation; good to a minute or two, no network needed).
+// nothing here keeps state: which entry is current is always recomputed from "now", so restarts just pick up
+// wherever the day is at.
+use std::f64::consts::PI;
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::{DateTime, Days, NaiveDate, NaiveTime, TimeZone, Utc};
+
+use super::config::{Location, ScheduleEntry};
+
+#[derive(Copy, Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum SolarEvent {
+    Dawn, // civil twilight, sun 6° below the horizon
+    Sunrise,
+    Noon,
+    Sunset,
+    Dusk,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum ScheduleTime {
+    Clock { hour: u32, minute: u32 },
+    Solar(SolarEvent),
+}
+
+impl ScheduleTime {
+    pub fn is_solar(&self) -> bool {
+        matches!(self, ScheduleTime::Solar(_))
+    }
+}
+
+impl FromStr for ScheduleTime {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<ScheduleTime, String> {
+        let event = match s {
+            "dawn" => Some(SolarEvent::Dawn),
+            "sunrise" => Some(SolarEvent::Sunrise),
+            "noon" => Some(SolarEvent::Noon),
+            "sunset" => Some(SolarEvent::Sunset),
+            "dusk" => Some(SolarEvent::Dusk),
+            _ => None,
+        };
+        if let Some(event) = event {
+            return Ok(ScheduleTime::Solar(event));
+        }
+        match NaiveTime::parse_from_str(s, "%H:%M") {
+            Ok(t) => Ok(ScheduleTime::Clock { hour: chrono::Timelike::hour(&t), minute: chrono::Timelike::minute(&t) }),
+            Err(_) => Err(format!("expected HH:MM, dawn, sunrise, noon, sunset or dusk; got \"{s}\"")),
+        }
+    }
+}
+
+impl fmt::Display for ScheduleTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScheduleTime::Clock { hour, minute } => write!(f, "{hour:02}:{minute:02}"),
+            ScheduleTime::Solar(event) => write!(f, "{}", format!("{event:?}").to_lowercase()),
+        }
+    }
+}
+
+fn julian_day(date: NaiveDate) -> f64 {
+    // julian date at 12:00 UTC on `date`
+    let unix_days = date.signed_duration_since(NaiveDate::from_ymd_opt(1970, 1, 1).unwrap()).num_days() as f64;
+    unix_days + 2440588.0
+}
+
+fn from_julian(jd: f64) -> Option<DateTime<Utc>> {
+    let secs = (jd - 2440587.5) * 86400.0;
+    DateTime::from_timestamp(secs.floor() as i64, 0)
+}
+
+// None if the sun never gets there that day (polar day/night)
+pub fn solar_event(event: SolarEvent, date: NaiveDate, loc: &Location) -> Option<DateTime<Utc>> {
+    let rad = PI / 180.0;
+    let n = julian_day(date) - 2451545.0 + 0.0008;
+    let mean_noon = n - loc.longitude / 360.0;
+    let anomaly = (357.5291 + 0.98560028 * mean_noon).rem_euclid(360.0);
+    let m = anomaly * rad;
+    let center = 1.9148 * m.sin() + 0.0200 * (2.0 * m).sin() + 0.0003 * (3.0 * m).sin();
+    let ecliptic_lon = ((anomaly + center + 180.0 + 102.9372).rem_euclid(360.0)) * rad;
+    let transit = 2451545.0 + mean_noon + 0.0053 * m.sin() - 0.0069 * (2.0 * ecliptic_lon).sin();
+    if event == SolarEvent::Noon {
+        return from_julian(transit);
+    }
+    let declination = (ecliptic_lon.sin() * (23.4397 * rad).sin()).asin();
+    let elevation: f64 = match event {
+        SolarEvent::Dawn | SolarEvent::Dusk => -6.0,
+        _ => -0.833, // refraction + the sun's radius
+    };
+    let lat = loc.latitude * rad;
+    let cos_hour_angle = ((elevation * rad).sin() - lat.sin() * declination.sin()) / (lat.cos() * declination.cos());
+    if !(-1.0..=1.0).contains(&cos_hour_angle) {
+        return None;
+    }
+    let hour_angle = cos_hour_angle.acos() / rad;
+    match event {
+        SolarEvent::Dawn | SolarEvent::Sunrise => from_julian(transit - hour_angle / 360.0),
+        _ => from_julian(transit + hour_angle / 360.0),
+    }
+}
+
+// when `entry` happens on the local date `date`, if it does at all
+fn occurrence<Tz: TimeZone>(entry: &ScheduleEntry, date: NaiveDate, tz: &Tz, loc: Option<&Location>) -> Option<DateTime<Tz>> {
+    let at = match entry.time {
+        ScheduleTime::Clock { hour, minute } => {
+            let naive = date.and_hms_opt(hour, minute, 0)?;
+            // an hour that DST skips over happens an hour late instead
+            tz.from_local_datetime(&naive).earliest()
+                .or_else(|| tz.from_local_datetime(&(naive + chrono::Duration::hours(1))).earliest())?
+        },
+        ScheduleTime::Solar(event) => solar_event(event, date, loc?)?.with_timezone(tz),
+    };
+    Some(at + chrono::Duration::minutes(entry.offset.unwrap_or(0)))
+}
+
+// the entry that should be showing at `now`, and when the next one takes over.
+// looks a day either side, so the last entry of yesterday carries on until the first one today
+pub fn current_entry<'a, Tz: TimeZone>(entries: &'a [ScheduleEntry], loc: Option<&Location>, now: &DateTime<Tz>) -> (Option<&'a ScheduleEntry>, Option<DateTime<Tz>>) {
+    let tz = now.timezone();
+    let today = now.date_naive();
+    let mut current: Option<(DateTime<Tz>, &ScheduleEntry)> = None;
+    let mut next: Option<DateTime<Tz>> = None;
+    for date in [today.checked_sub_days(Days::new(1)), Some(today), today.checked_add_days(Days::new(1))].into_iter().flatten() {
+        for entry in entries {
+            let at = match occurrence(entry, date, &tz, loc) {
+                Some(at) => at,
+                None => continue,
+            };
+            if at <= *now {
+                if current.as_ref().is_none_or(|(t, _)| at >= *t) {
+                    current = Some((at, entry));
+                }
+            } else if next.as_ref().is_none_or(|t| at < *t) {
+                next = Some(at);
+            }
+        }
+    }
+    (current.map(|(_, entry)| entry), next)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::FixedOffset;
+
+    const AMSTERDAM: Location = Location { latitude: 52.37, longitude: 4.90 };
+
+    fn minutes_between(a: DateTime<Utc>, b: DateTime<Utc>) -> i64 {
+        (a - b).num_minutes().abs()
+    }
+
+    #[test]
+    fn solar_events_match_published_times() {
+        let date = NaiveDate::from_ymd_opt(2024, 6, 21).unwrap();
+        // timeanddate.com: sunrise 05:18, sunset 22:06 CEST
+        let sunrise = solar_event(SolarEvent::Sunrise, date, &AMSTERDAM).unwrap();
+        let sunset = solar_event(SolarEvent::Sunset, date, &AMSTERDAM).unwrap();
+        assert!(minutes_between(sunrise, Utc.with_ymd_and_hms(2024, 6, 21, 3, 18, 0).unwrap()) <= 3);
+        assert!(minutes_between(sunset, Utc.with_ymd_and_hms(2024, 6, 21, 20, 6, 0).unwrap()) <= 3);
+        // midnight sun in svalbard
+        let svalbard = Location { latitude: 78.22, longitude: 15.65 };
+        assert_eq!(solar_event(SolarEvent::Sunset, date, &svalbard), None);
+    }
+
+    #[test]
+    fn picks_current_entry_across_midnight() {
+        let tz = FixedOffset::east_opt(2 * 3600).unwrap();
+        let entry = |time: &str, image: &str| ScheduleEntry { time: time.parse().unwrap(), image: image.to_string(), offset: None };
+        let entries = vec![entry("07:00", "day"), entry("sunset", "dusk"), entry("23:30", "night")];
+        let at = |h, m| tz.with_ymd_and_hms(2024, 6, 21, h, m, 0).unwrap();
+
+        let (current, next) = current_entry(&entries, Some(&AMSTERDAM), &at(3, 0));
+        assert_eq!(current.unwrap().image, "night");
+        assert_eq!(next, Some(at(7, 0)));
+
+        let (current, next) = current_entry(&entries, Some(&AMSTERDAM), &at(12, 0));
+        assert_eq!(current.unwrap().image, "day");
+        assert!(next.unwrap() > at(22, 0) && next.unwrap() < at(22, 10));
+
+        let (current, _) = current_entry(&entries, Some(&AMSTERDAM), &at(22, 30));
+        assert_eq!(current.unwrap().image, "dusk");
+    }
+}
@@ -0,0 +1,242 @@
+// rotation state for an `image` that points at a directory or glob.
+// the source is re-listed every time we pick a new image, so files dropped into the directory join in on their own.
+use super::config::{ImageSource, SlideshowOrder};
+
+use std::collections::{HashSet, VecDeque};
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+const DEFAULT_INTERVAL_SECS: u64 = 600;
+const HISTORY_LEN: usize = 64; // how far back `prev` can go
+
+fn is_image(path: &Path) -> bool {
+    path.is_file() && super::decoders::has_image_extension(path)
+}
+
+fn walk(dir: &Path, recursive: bool, found: &mut Vec<String>) {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            if recursive {
+                walk(&path, recursive, found);
+            }
+        } else if is_image(&path) {
+            found.push(path.to_string_lossy().to_string());
+        }
+    }
+}
+
+// every image a slideshow source currently covers, sorted
+pub fn list_images(source: &ImageSource) -> Vec<String> {
+    let recursive = source.recursive.unwrap_or(false);
+    let mut found = Vec::new();
+    let path = Path::new(&source.path);
+    if path.is_dir() {
+        walk(path, recursive, &mut found);
+    } else if let Ok(paths) = glob::glob(&source.path) {
+        for path in paths.flatten() {
+            if path.is_dir() {
+                if recursive {
+                    walk(&path, recursive, &mut found);
+                }
+            } else if is_image(&path) {
+                found.push(path.to_string_lossy().to_string());
+            }
+        }
+    }
+    found.sort();
+    found.dedup();
+    found
+}
+
+pub struct Slideshow {
+    pub source: ImageSource,
+    pub paused: bool,
+    pub next_at: Instant,
+    history: VecDeque<String>,
+    cursor: usize, // index into history of what's on screen
+    upcoming: Option<String>, // picked ahead of time so it can be preloaded
+    round: HashSet<String>, // shuffle-no-repeat: shown since the last reshuffle
+}
+
+impl Slideshow {
+    pub fn new(source: ImageSource) -> Slideshow {
+        let mut show = Slideshow {
+            source,
+            paused: false,
+            next_at: Instant::now(),
+            history: VecDeque::new(),
+            cursor: 0,
+            upcoming: None,
+            round: HashSet::new(),
+        };
+        show.next_at = Instant::now() + show.interval();
+        show
+    }
+
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs(self.source.interval.unwrap_or(DEFAULT_INTERVAL_SECS).max(1))
+    }
+
+    // None if the source doesn't contain any images (yet)
+    pub fn current(&mut self) -> Option<String> {
+        if self.history.is_empty() {
+            return self.advance();
+        }
+        self.history.get(self.cursor).cloned()
+    }
+
+    pub fn advance(&mut self) -> Option<String> {
+        self.next_at = Instant::now() + self.interval();
+        // walk forwards again after going back
+        if self.cursor + 1 < self.history.len() {
+            self.cursor += 1;
+            return self.history.get(self.cursor).cloned();
+        }
+        let next = match self.upcoming.take() {
+            Some(next) if Path::new(&next).exists() => next,
+            _ => self.pick()?,
+        };
+        self.history.push_back(next.clone());
+        if self.history.len() > HISTORY_LEN {
+            self.history.pop_front();
+        }
+        self.cursor = self.history.len() - 1;
+        Some(next)
+    }
+
+    pub fn back(&mut self) -> Option<String> {
+        self.next_at = Instant::now() + self.interval();
+        if self.cursor > 0 {
+            self.cursor -= 1;
+        }
+        self.history.get(self.cursor).cloned()
+    }
+
+    // what's on screen, without picking anything if nothing is yet
+    pub fn shown(&self) -> Option<String> {
+        self.history.get(self.cursor).cloned()
+    }
+
+    // picks up where a previous run left off; a saved image that's gone since is just ignored
+    pub fn resume(&mut self, image: Option<String>, paused: bool) {
+        if let Some(image) = image && Path::new(&image).exists() {
+            self.history = VecDeque::from([image]);
+            self.cursor = 0;
+        }
+        self.paused = paused;
+    }
+
+    // what advance() will show next
+    pub fn peek(&mut self) -> Option<String> {
+        if self.cursor + 1 < self.history.len() {
+            return self.history.get(self.cursor + 1).cloned();
+        }
+        if self.upcoming.is_none() {
+            self.upcoming = self.pick();
+        }
+        self.upcoming.clone()
+    }
+
+    // returns whether we're paused now
+    pub fn toggle_pause(&mut self) -> bool {
+        self.paused = !self.paused;
+        if !self.paused {
+            self.next_at = Instant::now() + self.interval();
+        }
+        self.paused
+    }
+
+    fn pick(&mut self) -> Option<String> {
+        let entries = list_images(&self.source);
+        if entries.is_empty() {
+            return None;
+        }
+        let current = self.history.get(self.cursor);
+        match self.source.order.unwrap_or(SlideshowOrder::Sequential) {
+            SlideshowOrder::Sequential => {
+                // next one after the current image by name, so additions/removals don't throw us off
+                let next = current.and_then(|c| entries.iter().find(|e| *e > c));
+                Some(next.unwrap_or(&entries[0]).clone())
+            },
+            SlideshowOrder::Shuffle => {
+                let candidates: Vec<&String> = entries.iter().filter(|e| Some(*e) != current).collect();
+                if candidates.is_empty() {
+                    return Some(entries[0].clone());
+                }
+                Some(candidates[fastrand::usize(..candidates.len())].clone())
+            },
+            SlideshowOrder::ShuffleNoRepeat => {
+                let mut candidates: Vec<&String> = entries.iter().filter(|e| !self.round.contains(*e)).collect();
+                if candidates.is_empty() {
+                    // new round; just don't start it with what's already on screen
+                    self.round.clear();
+                    candidates = entries.iter().filter(|e| Some(*e) != current).collect();
+                    if candidates.is_empty() {
+                        return Some(entries[0].clone());
+                    }
+                }
+                let next = candidates[fastrand::usize(..candidates.len())].clone();
+                self.round.insert(next.clone());
+                Some(next)
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn source(dir: &Path, order: SlideshowOrder) -> ImageSource {
+        ImageSource {
+            path: dir.to_string_lossy().to_string(),
+            interval: Some(60),
+            order: Some(order),
+            recursive: None,
+            ..ImageSource::default()
+        }
+    }
+
+    fn touch(dir: &Path, name: &str) -> String {
+        let path = dir.join(name);
+        fs::write(&path, []).unwrap();
+        path.to_string_lossy().to_string()
+    }
+
+    #[test]
+    fn sequential_wraps_and_picks_up_new_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = touch(dir.path(), "a.png");
+        let c = touch(dir.path(), "c.png");
+        touch(dir.path(), "notes.txt");
+        let mut show = Slideshow::new(source(dir.path(), SlideshowOrder::Sequential));
+        assert_eq!(show.current(), Some(a.clone()));
+        assert_eq!(show.advance(), Some(c.clone()));
+        assert_eq!(show.advance(), Some(a.clone()));
+        let b = touch(dir.path(), "b.png");
+        assert_eq!(show.advance(), Some(b.clone()));
+        assert_eq!(show.back(), Some(a.clone()));
+        assert_eq!(show.peek(), Some(b.clone()));
+        assert_eq!(show.advance(), Some(b));
+    }
+
+    #[test]
+    fn shuffle_no_repeat_shows_everything_once_per_round() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut all: Vec<String> = ["a.png", "b.png", "c.png", "d.png"].iter().map(|n| touch(dir.path(), n)).collect();
+        let mut show = Slideshow::new(source(dir.path(), SlideshowOrder::ShuffleNoRepeat));
+        let mut seen: Vec<String> = (0..4).map(|_| show.advance().unwrap()).collect();
+        seen.sort();
+        all.sort();
+        assert_eq!(seen, all);
+        // next round doesn't open with a repeat
+        let last = show.current();
+        assert_ne!(show.advance(), last);
+    }
+}
@@ -0,0 +1,95 @@
+// runtime overrides: what `pandora set`/`scroll` and slideshow next/prev/pause changed since the config was read.
+// kept in $XDG_STATE_HOME/pandora/state.json so they outlive restarts and config reloads, until `pandora reset`
+use super::commands::RenderMode;
+
+use serde::{Serialize, Deserialize};
+use std::collections::BTreeMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::{env, fs};
+
+pub fn get_state_dir() -> PathBuf {
+    let base_dir = match env::var("XDG_STATE_HOME") {
+        Ok(s) => shellexpand::full(&s).unwrap().into_owned(),
+        Err(_) => shellexpand::full("~/.local/state").unwrap().into_owned(),
+    };
+    Path::new(&base_dir).join("pandora")
+}
+
+pub fn get_state_file() -> PathBuf {
+    get_state_dir().join("state.json")
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct OutputOverride {
+    pub image: Option<String>,
+    pub mode: Option<RenderMode>,
+    pub scroll: Option<u32>, // where it was last scrolled to by hand
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct SlideshowState {
+    pub image: Option<String>,
+    pub paused: bool,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct RuntimeState {
+    // key: output name
+    #[serde(default)]
+    pub outputs: BTreeMap<String, OutputOverride>,
+    // key: the output's name, or its group's (see Pandora::slideshow_key)
+    #[serde(default)]
+    pub slideshows: BTreeMap<String, SlideshowState>,
+}
+
+impl RuntimeState {
+    // nothing saved yet is just no overrides
+    pub fn load(path: &Path) -> io::Result<RuntimeState> {
+        match fs::read_to_string(path) {
+            Ok(contents) => Ok(serde_json::from_str(&contents)?),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(RuntimeState::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    // written next to the real thing and renamed over it, so a crash mid-write can't leave half a file behind
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_string_pretty(self)?)?;
+        fs::rename(&tmp, path)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.outputs.is_empty() && self.slideshows.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn survives_a_round_trip_through_disk() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("pandora").join("state.json");
+        assert_eq!(RuntimeState::load(&path).unwrap(), RuntimeState::default());
+
+        let mut state = RuntimeState::default();
+        state.outputs.insert("DP-1".to_string(), OutputOverride {
+            image: Some("/a.png".to_string()),
+            mode: Some(RenderMode::ScrollVertical),
+            scroll: Some(420),
+        });
+        state.slideshows.insert("desk".to_string(), SlideshowState { image: Some("/b.png".to_string()), paused: true });
+        state.save(&path).unwrap();
+        assert_eq!(RuntimeState::load(&path).unwrap(), state);
+
+        // older files without a section still load
+        fs::write(&path, r#"{"outputs": {}}"#).unwrap();
+        assert!(RuntimeState::load(&path).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,96 @@
+// vector wallpapers. these are never scaled as bitmaps: every output/mode gets its own rasterization at exactly the
+// size get_new_image_dimensions asks for (we always render at the output's physical resolution, so its scale factor
+// doesn't come into it).
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::{Arc, OnceLock};
+
+use image::RgbaImage;
+use resvg::tiny_skia::{Pixmap, Transform};
+use resvg::usvg::{fontdb, Options, Tree};
+
+use super::error::DaemonError;
+
+pub fn is_svg(path: &Path) -> bool {
+    path.extension().is_some_and(|e| e.eq_ignore_ascii_case("svg") || e.eq_ignore_ascii_case("svgz"))
+}
+
+// loading system fonts takes a moment, so it's done once, on the first svg that needs it
+fn fonts() -> Arc<fontdb::Database> {
+    static FONTS: OnceLock<Arc<fontdb::Database>> = OnceLock::new();
+    FONTS.get_or_init(|| {
+        let mut db = fontdb::Database::new();
+        db.load_system_fonts();
+        Arc::new(db)
+    }).clone()
+}
+
+pub fn parse(path: &Path) -> Result<Tree, DaemonError> {
+    let data = fs::read(path)?;
+    let options = Options {
+        // so relative <image href>s work
+        resources_dir: path.parent().map(Path::to_path_buf),
+        fontdb: fonts(),
+        ..Options::default()
+    };
+    Ok(Tree::from_data(&data, &options)?)
+}
+
+// the size the document asks for, which is what the scaling maths works from
+pub fn dimensions(tree: &Tree) -> (u32, u32) {
+    let size = tree.size();
+    (size.width().round().max(1.0) as u32, size.height().round().max(1.0) as u32)
+}
+
+pub fn rasterize(tree: &Tree, width: u32, height: u32) -> Result<Pixmap, DaemonError> {
+    let mut pixmap = Pixmap::new(width, height).ok_or(DaemonError::LogicalError)?;
+    let size = tree.size();
+    let transform = Transform::from_scale(width as f32 / size.width(), height as f32 / size.height());
+    resvg::render(tree, transform, &mut pixmap.as_mut());
+    Ok(pixmap)
+}
+
+// wl_shm wants premultiplied BGRA, and premultiplied is what tiny-skia hands us; just swap the channels
+pub fn pixmap_into_buffer(pixmap: &Pixmap, f: &File) -> Result<(), DaemonError> {
+    let mut buf = BufWriter::new(f);
+    for px in pixmap.data().chunks_exact(4) {
+        buf.write_all(&[px[2], px[1], px[0], px[3]])?;
+    }
+    buf.flush()?;
+    Ok(())
+}
+
+// for things that want a plain image (the lockscreen etc)
+pub fn pixmap_into_image(pixmap: &Pixmap) -> RgbaImage {
+    let mut img = RgbaImage::new(pixmap.width(), pixmap.height());
+    for (px, out) in pixmap.pixels().iter().zip(img.pixels_mut()) {
+        let c = px.demultiply();
+        out.0 = [c.red(), c.green(), c.blue(), c.alpha()];
+    }
+    img
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Seek};
+
+    #[test]
+    fn rasterizes_at_requested_size() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("red.svg");
+        fs::write(&path, r##"<svg xmlns="http://www.w3.org/2000/svg" width="16" height="9"><rect width="16" height="9" fill="#f00"/></svg>"##).unwrap();
+        let tree = parse(&path).unwrap();
+        assert_eq!(dimensions(&tree), (16, 9));
+        let pixmap = rasterize(&tree, 1920, 1080).unwrap();
+        assert_eq!((pixmap.width(), pixmap.height()), (1920, 1080));
+
+        let mut f = tempfile::tempfile().unwrap();
+        pixmap_into_buffer(&pixmap, &f).unwrap();
+        f.rewind().unwrap();
+        let mut first = [0u8; 4];
+        f.read_exact(&mut first).unwrap();
+        assert_eq!(first, [0, 0, 255, 255]); // bgra
+    }
+}
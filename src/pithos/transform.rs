@@ -0,0 +1,125 @@
+// crop/rotate/flip from an output's image node. applied to the full-size image before any scaling maths happens, so
+// everything downstream (get_new_image_dimensions, the niri agent's scroll heights, the render viewport) only ever
+// sees the geometry after it. order is always crop, then rotate, then flip, whatever order the config lists them in;
+// an output group's slice (`region`) is cut out of the result last.
+use image::RgbaImage;
+use image::imageops;
+
+use super::config::{Crop, Flip};
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+pub struct Transform {
+    pub crop: Option<Crop>, // in the image's own pixels, after exif orientation
+    pub rotate: u32, // clockwise degrees: 0, 90, 180 or 270
+    pub flip: Option<Flip>,
+    pub region: Option<Crop>, // from the output's group, in the pixels of the image after everything above
+}
+
+impl Transform {
+    pub fn is_identity(&self) -> bool {
+        self.crop.is_none() && self.rotate == 0 && self.flip.is_none() && self.region.is_none()
+    }
+
+    // the part of a width x height image that's kept. a crop hanging off the edge is clamped to it; one that misses
+    // the image entirely is ignored rather than leaving nothing to show
+    fn crop_rect(crop: Option<Crop>, width: u32, height: u32) -> (u32, u32, u32, u32) {
+        match crop {
+            Some(c) if c.x < width && c.y < height && c.width > 0 && c.height > 0 => {
+                (c.x, c.y, c.width.min(width - c.x), c.height.min(height - c.y))
+            },
+            _ => (0, 0, width, height),
+        }
+    }
+
+    // what a width x height image comes out as
+    pub fn dimensions(&self, width: u32, height: u32) -> (u32, u32) {
+        let (_, _, width, height) = Transform::crop_rect(self.crop, width, height);
+        let (width, height) = match self.rotate {
+            90 | 270 => (height, width),
+            _ => (width, height),
+        };
+        let (_, _, width, height) = Transform::crop_rect(self.region, width, height);
+        (width, height)
+    }
+
+    // for cache keys; empty when there's nothing to do, so untransformed images keep their keys
+    pub fn cache_id(&self) -> String {
+        let mut id = String::new();
+        if let Some(c) = self.crop {
+            id += &format!("c{},{},{}x{}", c.x, c.y, c.width, c.height);
+        }
+        if self.rotate != 0 {
+            id += &format!("r{}", self.rotate);
+        }
+        match self.flip {
+            Some(Flip::Horizontal) => id += "fh",
+            Some(Flip::Vertical) => id += "fv",
+            None => (),
+        }
+        if let Some(c) = self.region {
+            id += &format!("g{},{},{}x{}", c.x, c.y, c.width, c.height);
+        }
+        id
+    }
+
+    // the same transform for a copy of the image drawn `factor` times its own size (svgs get rasterized straight at
+    // the size they're needed, so the crops have to follow)
+    pub fn scaled(&self, factor: f64) -> Transform {
+        let scale = |v: u32| (v as f64 * factor).round() as u32;
+        let scale_crop = |c: Crop| Crop { x: scale(c.x), y: scale(c.y), width: scale(c.width), height: scale(c.height) };
+        Transform {
+            crop: self.crop.map(scale_crop),
+            region: self.region.map(scale_crop),
+            ..*self
+        }
+    }
+
+    pub fn apply(&self, img: &RgbaImage) -> RgbaImage {
+        let (x, y, width, height) = Transform::crop_rect(self.crop, img.width(), img.height());
+        let mut img = imageops::crop_imm(img, x, y, width, height).to_image();
+        match self.rotate {
+            90 => img = imageops::rotate90(&img),
+            180 => imageops::rotate180_in_place(&mut img),
+            270 => img = imageops::rotate270(&img),
+            _ => (),
+        }
+        match self.flip {
+            Some(Flip::Horizontal) => imageops::flip_horizontal_in_place(&mut img),
+            Some(Flip::Vertical) => imageops::flip_vertical_in_place(&mut img),
+            None => (),
+        }
+        if self.region.is_some() {
+            let (x, y, width, height) = Transform::crop_rect(self.region, img.width(), img.height());
+            img = imageops::crop_imm(&img, x, y, width, height).to_image();
+        }
+        img
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crops_then_rotates_then_flips() {
+        // 4 x 2, each pixel's red channel is its index
+        let img = RgbaImage::from_fn(4, 2, |x, y| image::Rgba([(y * 4 + x) as u8, 0, 0, 255]));
+        let transform = Transform {
+            crop: Some(Crop { x: 1, y: 0, width: 10, height: 2 }), // clamped to 3 x 2
+            rotate: 90,
+            flip: Some(Flip::Vertical),
+            region: None,
+        };
+        assert_eq!(transform.dimensions(4, 2), (2, 3));
+        let out = transform.apply(&img);
+        assert_eq!(out.dimensions(), (2, 3));
+        // cropped: [1 2 3 / 5 6 7], rotated: [5 1 / 6 2 / 7 3], flipped: [7 3 / 6 2 / 5 1]
+        let reds: Vec<u8> = out.pixels().map(|p| p.0[0]).collect();
+        assert_eq!(reds, vec![7, 3, 6, 2, 5, 1]);
+        assert_eq!(transform.cache_id(), "c1,0,10x2r90fv");
+
+        let outside = Transform { crop: Some(Crop { x: 9, y: 0, width: 1, height: 1 }), ..Transform::default() };
+        assert_eq!(outside.dimensions(4, 2), (4, 2));
+        assert!(Transform::default().is_identity());
+    }
+}
@@ -1,36 +1,54 @@
 use pandora::pithos::config::{get_config_dir, load_config};
+use pandora::pithos::commands::DaemonCommand;
 use crate::pandora::Pandora;
 
-use std::path::PathBuf;
-use std::sync::{mpsc, Arc, Weak};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc, Mutex, Weak};
 use std::thread;
+use std::time::{Duration, Instant};
 
-use notify::{Event, event::EventKind, event::ModifyKind, RecursiveMode, Result, Watcher};
+use notify::{Event, event::EventKind, event::ModifyKind, RecommendedWatcher, RecursiveMode, Result, Watcher};
+
+// saving an image tends to come in as a burst of events; let it settle before we go decode a half-written file
+const IMAGE_SETTLE_TIME: Duration = Duration::from_millis(250);
+
+// the watcher's one inbox: filesystem events from notify, and the daemon telling it what's on screen changed
+enum Wake {
+    Fs(Result<Event>),
+    Daemon(DaemonCommand),
+}
 
-#[derive(Clone)]
 pub struct ConfigWatcher {
+    inbox: mpsc::Sender<Wake>,
+    wakes: Arc<Mutex<Option<mpsc::Receiver<Wake>>>>,
 }
 
 impl ConfigWatcher {
     pub fn new() -> Arc<ConfigWatcher> {
+        let (inbox, wakes) = mpsc::channel::<Wake>();
         return Arc::new(ConfigWatcher {
+            inbox,
+            wakes: Arc::new(Mutex::new(Some(wakes))),
         });
     }
 
     pub fn start(&self, weak: Weak<Pandora>) {
         let p = weak.upgrade().take().unwrap();
-        thread::spawn(move || watch(&get_config_dir(), p));
+        let inbox = self.inbox.clone();
+        let wakes = self.wakes.lock().ok().and_then(|mut w| w.take()).expect("config watcher started twice");
+        thread::spawn(move || watch(&get_config_dir(), p, inbox, wakes));
     }
-}
 
-fn process_event(e: &Event) -> bool {
-    // return false if we dgaf about this event
-    if !e.paths.contains(&get_config_dir().join("pandora.kdl")) {
-        return false;
+    pub fn send(&self, cmd: DaemonCommand) {
+        let _ = self.inbox.send(Wake::Daemon(cmd));
     }
+}
+
+fn is_write_event(e: &Event) -> bool {
     match e.kind {
         EventKind::Create(_) => return true,
-        EventKind::Modify(modkind) => { 
+        EventKind::Modify(modkind) => {
             match modkind {
                 ModifyKind::Data(_) => return true,
                 ModifyKind::Name(_) => return true, // vim-type tmp file -> rename clobber, probably
@@ -41,26 +59,126 @@ fn process_event(e: &Event) -> bool {
     }
 }
 
-fn watch(path: &PathBuf, pandora: Arc<Pandora>) {
-    let (tx, rx) = mpsc::channel::<Result<Event>>();
-    let mut watcher = notify::recommended_watcher(tx).expect("Could not create a watcher for config dir");
+fn process_event(e: &Event) -> bool {
+    // return false if we dgaf about this event
+    if !e.paths.contains(&get_config_dir().join("pandora.kdl")) {
+        return false;
+    }
+    is_write_event(e)
+}
+
+// the wallpapers the active config refers to, plus whatever's actually on screen (`pandora set`, the state file,
+// variants picked out of wallpaper packages..). we watch their parent dirs rather than the files themselves,
+// since plenty of tools save by renaming over the old file (which would orphan a watch on the old inode)
+#[derive(Default)]
+struct ImageWatches {
+    images: HashSet<PathBuf>,
+    dirs: HashSet<PathBuf>,
+    pending: HashMap<PathBuf, Instant>,
+}
+
+impl ImageWatches {
+    fn update(&mut self, watcher: &mut RecommendedWatcher, config_dir: &Path, pandora: &Pandora) {
+        let mut paths = match pandora.config() {
+            Ok(conf) => conf.image_paths(),
+            Err(e) => {
+                pandora.log("config-watcher", format!("could not read config for image watches: {e:?}"));
+                return;
+            },
+        };
+        paths.extend(pandora.shown_images());
+        self.images = paths.iter().map(PathBuf::from).collect();
+        // anything under the config dir is already covered by its recursive watch
+        let dirs: HashSet<PathBuf> = self.images.iter()
+            .filter_map(|p| p.parent().map(Path::to_path_buf))
+            .filter(|d| !d.starts_with(config_dir))
+            .collect();
+        for dir in self.dirs.difference(&dirs) {
+            let _ = watcher.unwatch(dir);
+        }
+        for dir in dirs.difference(&self.dirs) {
+            if let Err(e) = watcher.watch(dir, RecursiveMode::NonRecursive) {
+                pandora.log("config-watcher", format!("could not watch {} for image changes: {e:?}", dir.display()));
+            }
+        }
+        self.dirs = dirs;
+        self.pending.retain(|p, _| self.images.contains(p));
+    }
+
+    fn note(&mut self, e: &Event) {
+        if !is_write_event(e) {
+            return;
+        }
+        for path in &e.paths {
+            if self.images.contains(path) {
+                self.pending.insert(path.clone(), Instant::now());
+            }
+        }
+    }
+
+    fn next_deadline(&self) -> Option<Instant> {
+        self.pending.values().min().map(|t| *t + IMAGE_SETTLE_TIME)
+    }
+
+    fn take_settled(&mut self) -> Vec<PathBuf> {
+        let now = Instant::now();
+        let settled: Vec<PathBuf> = self.pending.iter()
+            .filter(|(_, t)| now.duration_since(**t) >= IMAGE_SETTLE_TIME)
+            .map(|(p, _)| p.clone())
+            .collect();
+        for path in &settled {
+            self.pending.remove(path);
+        }
+        settled
+    }
+}
+
+fn watch(path: &PathBuf, pandora: Arc<Pandora>, inbox: mpsc::Sender<Wake>, rx: mpsc::Receiver<Wake>) {
+    let mut watcher = notify::recommended_watcher(move |res| _ = inbox.send(Wake::Fs(res)))
+        .expect("Could not create a watcher for config dir");
     watcher.watch(path, RecursiveMode::Recursive).expect("Could not start watcher for config dir");
-    for res in rx {
+    let mut images = ImageWatches::default();
+    images.update(&mut watcher, path, &pandora);
+    loop {
+        // only wake up on a timer if there's an image change waiting to settle
+        let res = match images.next_deadline() {
+            Some(deadline) => match rx.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                Ok(res) => Some(res),
+                Err(mpsc::RecvTimeoutError::Timeout) => None,
+                Err(mpsc::RecvTimeoutError::Disconnected) => break,
+            },
+            None => match rx.recv() {
+                Ok(res) => Some(res),
+                Err(_) => break,
+            },
+        };
         match res {
-            Ok(e) => {
-                if !process_event(&e) {continue};
-                
-                match load_config() {
-                    Ok(conf) => {
-                        pandora.handle_cmd(
-                            &pandora::pithos::commands::CommandType::Dc(
-                                pandora::pithos::commands::DaemonCommand::ReloadConfig(
-                                    conf)));
-                    },
-                    Err(e) => pandora.log("config-watcher", format!("{e:?}")),
-                };
+            Some(Wake::Fs(Ok(e))) => {
+                images.note(&e);
+                if process_event(&e) {
+                    match load_config() {
+                        Ok(conf) => {
+                            pandora.handle_cmd(
+                                &pandora::pithos::commands::CommandType::Dc(
+                                    pandora::pithos::commands::DaemonCommand::ReloadConfig(
+                                        conf)));
+                            images.update(&mut watcher, path, &pandora);
+                        },
+                        Err(e) => pandora.log("config-watcher", format!("{e:?}")),
+                    };
+                }
             }
-            Err(e) => pandora.log("config-watcher", format!("watch error: {e:?}")),
+            Some(Wake::Fs(Err(e))) => pandora.log("config-watcher", format!("watch error: {e:?}")),
+            // something new went on screen
+            Some(Wake::Daemon(DaemonCommand::ImageChanged(_))) => images.update(&mut watcher, path, &pandora),
+            Some(Wake::Daemon(_)) => (),
+            None => (),
         };
+        for image in images.take_settled() {
+            let image = image.to_string_lossy().to_string();
+            if let Err(e) = pandora.reload_image(&image) {
+                pandora.log("config-watcher", format!("could not reload {image}: {e:?}"));
+            }
+        }
     }
-}
\ No newline at end of file
+}
@@ -1,33 +1,56 @@
 use std::sync::{Arc, Weak};
 use std::thread;
-use std::os::linux::net::SocketAddrExt;
-use std::os::unix::net::{SocketAddr, UnixListener};
+use std::os::unix::net::{UnixListener, UnixStream};
 
 use crate::pandora::Pandora;
+use pandora_ipc::sockets::{peer_uid, socket_addr, write_response_to_client_socket, ErrorCode, Response};
 
 #[derive(Clone)]
 pub struct InboundCommandHandler {
     listener: Arc<UnixListener>,
+    name: String,
 }
 
 impl InboundCommandHandler {
-    pub fn new() -> Arc<InboundCommandHandler> {
-        let listen_addr = SocketAddr::from_abstract_name("pandora").expect(
-            "could not construct linux named-socket address (sorry bsd?)");
-        let socket = UnixListener::bind_addr(&listen_addr).expect(
-            "failed to bind to named socket (already running?)");
+    pub fn new(socket_name: &str) -> Arc<InboundCommandHandler> {
+        let listen_addr = socket_addr(socket_name).expect(
+            "could not construct socket address for ipc");
+        // a socket file left behind by a daemon that didn't get to clean up; abstract names go away by themselves
+        if socket_name.contains('/') && UnixStream::connect_addr(&listen_addr).is_err() {
+            let _ = std::fs::remove_file(socket_name);
+        }
+        let socket = UnixListener::bind_addr(&listen_addr).unwrap_or_else(|e| panic!(
+            "failed to bind to ipc socket {socket_name} (already running?): {e}"));
 
         return Arc::new(InboundCommandHandler {
             listener: Arc::new(socket),
+            name: socket_name.to_string(),
         });
     }
 
     pub fn start(&self, pandora: Weak<Pandora>) {
+        if let Some(p) = pandora.upgrade() {
+            p.log("ipc", format!("listening on {}", self.name));
+        }
         for connection in self.listener.incoming() {
             let p = pandora.upgrade().take().unwrap();
-            thread::spawn(move || 
-                p.process_ipc(&connection.expect(
-                    "could not accept incoming client socket/connection")));
+            let socket = match connection {
+                Ok(socket) => socket,
+                Err(e) => {
+                    p.log("ipc", format!("could not accept client connection: {e}"));
+                    continue;
+                },
+            };
+            // abstract sockets have no file permissions, so anyone in the network namespace can connect
+            match peer_uid(&socket) {
+                Ok(uid) if p.ipc_allowed(uid) => _ = thread::spawn(move || p.process_ipc(&socket)),
+                Ok(uid) => {
+                    p.log("ipc", format!("refused a connection from uid {uid} (see `allow-uid` in the config's ipc node)"));
+                    let refusal = Response::err(ErrorCode::PermissionDenied, format!("uid {uid} may not control this daemon"));
+                    let _ = write_response_to_client_socket(&refusal, &socket);
+                },
+                Err(e) => p.log("ipc", format!("refused a connection whose credentials couldn't be checked: {e}")),
+            }
         }
     }
 }
\ No newline at end of file
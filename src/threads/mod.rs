@@ -5,5 +5,7 @@ pub mod logger; // logChamp
 pub mod niri;
 pub mod outputs;
 pub mod render;
+pub mod schedule;
+pub mod slideshow;
 
 // todo: define trait here, refactor all impl to reflect that
\ No newline at end of file
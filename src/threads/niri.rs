@@ -2,7 +2,11 @@ use crate::pandora::Pandora;
 use pandora::pithos::commands::RenderCommand;
 use ::pandora::pithos::commands::{CommandType, DaemonCommand, ModeCommand, RenderMode, RenderThreadCommand, ScrollCommand};
 use ::pandora::pithos::config::DaemonConfig;
+use ::pandora::pithos::error::{CommandError, DaemonError};
+use pandora_ipc::sockets::ErrorCode;
+use ::pandora::pithos::layout::LogicalRect;
 use ::pandora::pithos::misc::get_new_image_dimensions;
+use ::pandora::pithos::transform::Transform;
 
 use std::collections::HashMap;
 use std::ops::Index;
@@ -65,6 +69,15 @@ fn get_niri_state(socket: &mut Socket) -> (HashMap<String, Output>, Vec<Workspac
     return (outputs_response, workspaces_response);
 }
 
+// a fresh look, for `pandora status`; the agent's own copy lives on its thread
+pub fn workspaces() -> Result<Vec<Workspace>, DaemonError> {
+    match Socket::connect()?.send(Request::Workspaces)? {
+        Ok(Response::Workspaces(workspaces)) => Ok(workspaces),
+        Ok(_) => Err(CommandError::coded(ErrorCode::Internal, "niri answered a workspaces request with something else")),
+        Err(e) => Err(CommandError::coded(ErrorCode::Internal, &format!("niri: {e}"))),
+    }
+}
+
 fn run(config: DaemonConfig, pandora: Arc<Pandora>, cmd_queue: Arc<Mutex<Receiver<DaemonCommand>>>) {
     let mut socket = Socket::connect().unwrap();
     let mut processor = NiriProcessor::default();
@@ -79,24 +92,28 @@ fn run(config: DaemonConfig, pandora: Arc<Pandora>, cmd_queue: Arc<Mutex<Receive
             processor.process(pandora.clone(), event);
             match cmd_queue.lock() {
                 Ok(channel) => {
-                    match channel.try_recv() {
-                        Ok(cmd) => {
-                            match cmd {
-                                DaemonCommand::OutputModeChange(new_mode) => {
-                                    // update state => reflow output
-                                    processor.update_mode(new_mode);
+                    // drain everything that piled up since the last niri event
+                    while let Ok(cmd) = channel.try_recv() {
+                        match cmd {
+                            DaemonCommand::OutputModeChange(new_mode) => {
+                                // update state => reflow output
+                                processor.update_mode(new_mode);
+                                processor.reseat_scroll_positions(pandora.clone());
+                            },
+                            DaemonCommand::ReloadConfig(config) => {
+                                if processor.update_config(config, pandora.clone()) {
                                     processor.reseat_scroll_positions(pandora.clone());
-                                },
-                                DaemonCommand::ReloadConfig(config) => {
-                                    if processor.update_config(config, pandora.clone()) {
-                                        processor.reseat_scroll_positions(pandora.clone());
-                                    }
                                 }
-                                DaemonCommand::Lock => (), // i think ? 
-                                DaemonCommand::LoadImage(_) | DaemonCommand::Stop => (),
                             }
-                        },
-                        Err(_) => (), // ??
+                            DaemonCommand::ImageChanged(c) => {
+                                // slideshow moved on / file changed under us: new image, possibly a new height
+                                if processor.image_changed(c, pandora.clone()) {
+                                    processor.reseat_scroll_positions(pandora.clone());
+                                }
+                            }
+                            DaemonCommand::Lock => (), // i think ? 
+                            DaemonCommand::LoadImage(_) | DaemonCommand::Stop | DaemonCommand::Slideshow(_) => (),
+                        }
                     }
                 },
                 Err(e) => {
@@ -119,6 +136,30 @@ struct OutputState {
     max_workspace_idx: u8, // idx, name
 }
 
+impl OutputState {
+    // recomputes the scaled image size for a new image/mode. false if the image couldn't be read
+    fn set_image(&mut self, image: &String, mode: RenderMode, transform: &Transform, pandora: &Pandora) -> bool {
+        let (image_width, image_height) = match pandora.transformed_dimensions(image, transform) {
+            Ok((w, h)) => (w, h),
+            Err(_) => return false,
+        };
+
+        let (scale_width, scale_height) = match &mode {
+            RenderMode::Static => (Some(self.width as u32), Some(self.height as u32)),
+            RenderMode::ScrollVertical => (Some(self.width as u32), None),
+            RenderMode::ScrollLateral => (None, Some(self.height as u32))
+        };
+
+        let (scaled_width, scaled_height) = get_new_image_dimensions(image_width, image_height, scale_width, scale_height);
+
+        self._current_image = image.clone();
+        self.mode = Some(mode);
+        self._img_width = scaled_width as i32;
+        self.img_height = scaled_height as i32;
+        true
+    }
+}
+
 #[derive(Default)]
 struct NiriProcessor {
     config: DaemonConfig,
@@ -131,7 +172,7 @@ impl NiriProcessor {
         // this kinda sucks and i should really really rewrite it into an "update state" or something
         let mut mutated = false;
         for new_output_conf in &new_config.outputs {
-            let new_mode = new_output_conf.mode.unwrap_or(RenderMode::Static);
+            let new_mode = pandora.resolve_mode(new_output_conf);
             let (output_name, state) = match self.outputs.iter_mut()
             .find(|o| o.0 == new_output_conf.name) {
                 Some(v) => v,
@@ -142,32 +183,27 @@ impl NiriProcessor {
             // live config reloading for the end users...... they know not nor care not about my sins, probably
             // next time any of this code needs any touching it *shall* be refactored into an UpdateState internal func
             // that the other functions leverage sanely
-            if state._current_image != new_output_conf.image || state.mode.unwrap_or(RenderMode::Static) != new_mode {
+            let image = pandora.resolve_image(new_output_conf);
+            let transform = pandora.output_transform(output_name, &image);
+            let (effects_changed, transform_changed, layers_changed) = self.config.outputs.iter()
+                .find(|o| o.name == new_output_conf.name)
+                .map_or((false, false, false), |o| (
+                    o.effects != new_output_conf.effects,
+                    o.image.transform() != new_output_conf.image.transform(),
+                    o.layers != new_output_conf.layers,
+                ));
+            // bezels, membership..
+            let group_changed = self.config.group_of(output_name) != new_config.group_of(output_name);
+            if state._current_image != image || state.mode.unwrap_or(RenderMode::Static) != new_mode
+                || effects_changed || transform_changed || layers_changed || group_changed {
                 // really hacky state updating in place. brittle. YEEHAW
-                if pandora.load_image(&new_output_conf.image.clone()).is_err() {
-                    pandora.log("niri-agent", format!("failed to load {} for {} (does it exist?)", new_output_conf.image.clone(), new_output_conf.name.clone()));
+                if !state.set_image(&image, new_mode, &transform, &pandora) {
+                    pandora.log("niri-agent", format!("failed to load {} for {} (does it exist?)", image, new_output_conf.name.clone()));
                     continue; // !
                 }
-                let (image_width, image_height) = match pandora.get_image_dimensions(new_output_conf.image.clone()) {
-                    Ok((w, h)) => (w, h),
-                    Err(_) => unreachable!(), // LoadImage should've exploded
-                };
-
-                let (scale_width, scale_height) = match &new_mode {
-                    RenderMode::Static => (Some(state.width as u32), Some(state.height as u32)),
-                    RenderMode::ScrollVertical => (Some(state.width as u32), None),
-                    RenderMode::ScrollLateral => (None, Some(state.height as u32))
-                };
-
-                let (scaled_width, scaled_height) = get_new_image_dimensions(image_width, image_height, scale_width, scale_height);
-
-                state._current_image = new_output_conf.image.clone();
-                state.mode = Some(new_mode);
-                state._img_width = scaled_width as i32;
-                state.img_height = scaled_height as i32;
                 let cmd = RenderCommand {
                     output: output_name.clone(),
-                    image: new_output_conf.image.clone(),
+                    image,
                     mode: new_mode,
                 };
                 pandora.handle_cmd(&CommandType::Tc(RenderThreadCommand::Render(cmd)));
@@ -178,6 +214,21 @@ impl NiriProcessor {
         return mutated;
     }
 
+    // a render went out that we didn't send ourselves; catch up on what's on screen. returns whether anything changed.
+    // same image + mode can still mean a new size, if it's a group member whose slice got re-cut
+    fn image_changed(&mut self, c: RenderCommand, pandora: Arc<Pandora>) -> bool {
+        let state = match self.outputs.iter_mut().find(|o| o.0 == c.output) {
+            Some(o) => &mut o.1,
+            None => return false,
+        };
+        let before = (state._current_image.clone(), state.mode, state.img_height);
+        if !state.set_image(&c.image, c.mode, &pandora.output_transform(&c.output, &c.image), &pandora) {
+            pandora.log("niri-agent", format!("could not read dimensions of {} for {}", c.image, c.output));
+            return false;
+        }
+        (state._current_image.clone(), state.mode, state.img_height) != before
+    }
+
     fn update_mode(&mut self, new_mode: ModeCommand) {
         self.outputs.iter_mut()
         .find(|o| o.0 == new_mode.output)
@@ -215,22 +266,27 @@ impl NiriProcessor {
                 let mode_idx = output.current_mode.unwrap();
                 let mode = output.modes.index(mode_idx);
                 let (output_width, output_height) = (mode.width as u32, mode.height as u32);
-                let (scale_width, scale_height) = match &output_config.mode {
-                    None => (Some(output_width), Some(output_height)),
-                    Some(mode) => match mode {
-                        RenderMode::Static => (Some(output_width), Some(output_height)),
-                        RenderMode::ScrollVertical => (Some(output_width), None),
-                        RenderMode::ScrollLateral => (None, Some(output_height))
-                    },
+                pandora.set_output_size(&output_name, output_width, output_height);
+                if let Some(logical) = output.logical {
+                    pandora.set_output_layout(&output_name, LogicalRect { x: logical.x, y: logical.y, width: logical.width, height: logical.height });
+                }
+                let render_mode = pandora.resolve_mode(output_config);
+                let (scale_width, scale_height) = match render_mode {
+                    RenderMode::Static => (Some(output_width), Some(output_height)),
+                    RenderMode::ScrollVertical => (Some(output_width), None),
+                    RenderMode::ScrollLateral => (None, Some(output_height))
                 };
 
-                let img_path = output_config.image.clone();
+                let img_path = pandora.resolve_image(output_config);
 
-                pandora.load_image(&img_path).unwrap(); // can explode on invalid images l0l
-
-                let (image_width, image_height) = match pandora.get_image_dimensions(img_path.clone()) {
-                    Ok((w, h)) => (w, h),
-                    Err(_) => unreachable!(), // LoadImage should've exploded
+                // only reads the header; the render thread decodes (or pulls it from the disk cache)
+                let (image_width, image_height) = match pandora.transformed_dimensions(&img_path, &pandora.output_transform(&output_name, &img_path)) {
+                    Ok(dims) => dims,
+                    Err(e) => {
+                        // render will refuse it too; scroll as if it fit the output until a good image shows up
+                        pandora.log("niri-agent", format!("could not read {img_path} for {output_name}: {e:?}"));
+                        (output_width, output_height)
+                    },
                 };
                
                 let (scaled_width, scaled_height) = get_new_image_dimensions(image_width, image_height, scale_width, scale_height);
@@ -241,7 +297,7 @@ impl NiriProcessor {
                     _current_image: img_path,
                     _img_width: scaled_width as i32,
                     img_height: scaled_height as i32,
-                    mode: output_config.mode.clone(),
+                    mode: Some(render_mode),
                     max_workspace_idx: 0,
                 };
                 self.outputs.push((output_name.clone(), output_state));
@@ -276,6 +332,24 @@ impl NiriProcessor {
         }
     }
     
+    // the rest of an output group follows whichever member last scrolled, to the same fraction of the way down
+    fn sync_group_scroll(&self, pandora: &Pandora, output_name: &String, fraction: f64) {
+        let group = match self.config.group_of(output_name) {
+            Some(g) => g,
+            None => return,
+        };
+        for (name, state) in self.outputs.iter().filter(|o| o.0 != *output_name && group.outputs.contains(&o.0)) {
+            if state.mode != Some(RenderMode::ScrollVertical) {
+                continue;
+            }
+            let position = ((state.img_height - state.height).max(0) as f64 * fraction).round() as u32;
+            pandora.handle_cmd(&CommandType::Tc(RenderThreadCommand::Scroll(ScrollCommand {
+                output: name.clone(),
+                position,
+            })));
+        }
+    }
+
     fn gen_scroll_cmd_for_workspace_id(&self, pandora: Arc<Pandora>, id: u64) {
         let workspace = self.workspaces.iter().find(|w| w.id == id).unwrap();
         let curr_idx = workspace.idx;
@@ -302,6 +376,9 @@ impl NiriProcessor {
                     // scroll dist should be min(that, output_height) so that if we have too few workspaces we scroll in a continuous manner
                     let scroll_per_workspace = output.height.min((last_scroll_pos - first_scroll_pos) / (output.max_workspace_idx - 1) as i32);
                     let pos = scroll_per_workspace as u32 * (curr_idx - 1) as u32;
+                    if last_scroll_pos > 0 {
+                        self.sync_group_scroll(&pandora, &output_name, pos as f64 / last_scroll_pos as f64);
+                    }
                     let cmd = RenderThreadCommand::Scroll(ScrollCommand {
                         output: output_name,
                         position: pos,
@@ -1,4 +1,6 @@
-use ::pandora::pithos::{config::DaemonConfig, commands::{CommandType, DaemonCommand, ModeCommand, RenderCommand, RenderMode, RenderThreadCommand, StopCommand}};
+use ::pandora::pithos::color::ColorSpace;
+use ::pandora::pithos::layout::LogicalRect;
+use ::pandora::pithos::{config::{DaemonConfig, OutputConfig}, commands::{CommandType, DaemonCommand, ModeCommand, RenderCommand, RenderThreadCommand, StopCommand}};
 
 use std::sync::{Arc, Mutex, Weak, mpsc::{channel, Receiver, Sender}};
 use std::thread;
@@ -7,6 +9,8 @@ use wayrs_client::global::GlobalExt;
 use wayrs_client::protocol::wl_output::{self, WlOutput};
 use wayrs_client::protocol::wl_registry::{self, GlobalArgs};
 use wayrs_client::{Connection, EventCtx, IoMode};
+use wayrs_protocols::color_management_v1::wp_color_manager_v1::{self, Feature, Primaries, RenderIntent, TransferFunction, WpColorManagerV1};
+use wayrs_protocols::xdg_output_unstable_v1::{zxdg_output_v1::{self, ZxdgOutputV1}, ZxdgOutputManagerV1};
 
 use crate::pandora::Pandora;
 
@@ -51,6 +55,12 @@ fn run(config: DaemonConfig, pandora: Arc<Pandora>, cmd_queue: Arc<Mutex<Receive
         conn.flush(IoMode::Blocking).unwrap();
         conn.recv_events(IoMode::Blocking).unwrap();
         conn.dispatch_events(&mut state);
+        // outputs are bound after everything else in the registry batch, so the color manager's capabilities
+        // come in ahead of the first output's done (and with them, the first render)
+        for global in std::mem::take(&mut state.unbound_outputs) {
+            let output = Output::bind(&mut conn, &global, state.xdg_output_manager);
+            state.outputs.push(output);
+        }
         match cmd_queue.lock() {
             Ok(channel) => {
                 match channel.try_recv() {
@@ -75,39 +85,89 @@ fn run(config: DaemonConfig, pandora: Arc<Pandora>, cmd_queue: Arc<Mutex<Receive
 #[derive(Default)]
 struct State {
     outputs: Vec<Output>,
+    unbound_outputs: Vec<GlobalArgs>,
+    color: ColorCapabilities,
+    xdg_output_manager: Option<ZxdgOutputManagerV1>,
     config: DaemonConfig,
     pandora: Option<Arc<Pandora>>,
 }
 
+// what wp_color_manager_v1 advertises, collected until its done event
+#[derive(Default)]
+struct ColorCapabilities {
+    manager: Option<WpColorManagerV1>,
+    parametric: bool,
+    perceptual: bool,
+    primaries: Vec<Primaries>,
+    transfer: Vec<TransferFunction>,
+}
+
+impl ColorCapabilities {
+    // the named spaces render threads can describe with a parametric image description
+    fn spaces(&self) -> Vec<ColorSpace> {
+        if !self.parametric || !self.perceptual {
+            return Vec::new();
+        }
+        ColorSpace::ALL.into_iter()
+            .filter(|s| self.primaries.contains(&s.primaries()) && self.transfer.contains(&s.transfer()))
+            .collect()
+    }
+}
+
 #[derive(Debug)]
 struct Output {
     registry_name: u32,
     wl_output: WlOutput,
     name: Option<String>,
+    size: Option<(u32, u32)>, // current mode
     done: bool,
+    // where it is in the compositor's layout, for output groups. xdg-output v3 sends these ahead of wl_output's done
+    xdg_output: Option<ZxdgOutputV1>,
+    logical_position: Option<(i32, i32)>,
+    logical_size: Option<(i32, i32)>,
 }
 
 impl Output {
-    fn bind(conn: &mut Connection<State>, global: &GlobalArgs) -> Self {
+    fn bind(conn: &mut Connection<State>, global: &GlobalArgs, xdg_output_manager: Option<ZxdgOutputManagerV1>) -> Self {
+        let wl_output = global.bind_with_cb(conn, 3..=4, wl_output_cb).unwrap();
         Self {
             registry_name: global.name,
-            wl_output: global.bind_with_cb(conn, 3..=4, wl_output_cb).unwrap(),
+            wl_output,
             name: None,
+            size: None,
             done: false,
+            xdg_output: xdg_output_manager.map(|m| m.get_xdg_output_with_cb(conn, wl_output, xdg_output_cb)),
+            logical_position: None,
+            logical_size: None,
         }
     }
+
+    fn logical(&self) -> Option<LogicalRect> {
+        let ((x, y), (width, height)) = (self.logical_position?, self.logical_size?);
+        Some(LogicalRect { x, y, width: width as u32, height: height as u32 })
+    }
 }
 
 fn wl_registry_cb(conn: &mut Connection<State>, state: &mut State, event: &wl_registry::Event) {
     match event {
         wl_registry::Event::Global(global) if global.is::<WlOutput>() => {
-            // kms lol
-            state.outputs.push(Output::bind(conn, global));
+            // kms lol (bound once the rest of the batch has been seen, see run)
+            state.unbound_outputs.push(global.clone());
+        },
+        wl_registry::Event::Global(global) if global.is::<WpColorManagerV1>() => {
+            state.color.manager = global.bind_with_cb(conn, 1..=1, color_manager_cb).ok();
+        },
+        wl_registry::Event::Global(global) if global.is::<ZxdgOutputManagerV1>() => {
+            state.xdg_output_manager = global.bind(conn, 1..=3).ok();
         },
         wl_registry::Event::GlobalRemove(name) => {
             if let Some(i) = state.outputs.iter().position(|o| o.registry_name == *name) {
                 let mut output = state.outputs.swap_remove(i);
                 let output_name = output.name.take().unwrap();
+                state.pandora.as_ref().unwrap().remove_output_layout(&output_name);
+                if let Some(xdg_output) = output.xdg_output.take() {
+                    xdg_output.destroy(conn);
+                }
                 let cmd = RenderThreadCommand::Stop(StopCommand {
                     output: output_name,
                 });
@@ -119,6 +179,21 @@ fn wl_registry_cb(conn: &mut Connection<State>, state: &mut State, event: &wl_re
     }
 }
 
+fn color_manager_cb(ctx: EventCtx<State, WpColorManagerV1>) {
+    let color = &mut ctx.state.color;
+    match ctx.event {
+        wp_color_manager_v1::Event::SupportedIntent(RenderIntent::Perceptual) => color.perceptual = true,
+        wp_color_manager_v1::Event::SupportedFeature(Feature::Parametric) => color.parametric = true,
+        wp_color_manager_v1::Event::SupportedPrimariesNamed(primaries) => color.primaries.push(primaries),
+        wp_color_manager_v1::Event::SupportedTfNamed(tf) => color.transfer.push(tf),
+        wp_color_manager_v1::Event::Done => {
+            let spaces = color.spaces();
+            ctx.state.pandora.as_ref().unwrap().set_color_spaces(spaces);
+        },
+        _ => (),
+    }
+}
+
 fn wl_output_cb(ctx: EventCtx<State, WlOutput>) {
     let pandora = ctx.state.pandora.as_ref().unwrap().clone();
     let output = &mut ctx
@@ -130,8 +205,14 @@ fn wl_output_cb(ctx: EventCtx<State, WlOutput>) {
 
     match ctx.event {
         wl_output::Event::Mode(new_mode) => {
+            if new_mode.flags.contains(wl_output::Mode::Current) {
+                output.size = Some((new_mode.width as u32, new_mode.height as u32));
+            }
             if output.done { // do not try to dispatch this during initial startup
                 let output_name = output.name.as_ref().unwrap().clone();
+                if let Some((width, height)) = output.size {
+                    pandora.set_output_size(&output_name, width, height);
+                }
                 let config_outputs = &ctx.state.config.outputs;
                 let output_config = match config_outputs.iter().find(|oc| oc.name == output_name) {
                     Some(conf) => conf,
@@ -140,16 +221,13 @@ fn wl_output_cb(ctx: EventCtx<State, WlOutput>) {
                         return;
                     },
                 };
-                let mode = match output_config.mode {
-                    Some(m) => m,
-                    None => RenderMode::Static,
-                };
+                let mode = pandora.resolve_mode(output_config);
                 let stop_cmd = RenderThreadCommand::Stop(StopCommand {
                     output: output_name.clone(),
                 });
                 let start_cmd = RenderThreadCommand::Render(RenderCommand {
                     output: output_name.clone(),
-                    image: output_config.image.clone(),
+                    image: pandora.resolve_image(output_config),
                     mode: mode,
                 });
                 let mode_cmd = DaemonCommand::OutputModeChange(ModeCommand {
@@ -165,31 +243,43 @@ fn wl_output_cb(ctx: EventCtx<State, WlOutput>) {
         wl_output::Event::Done => {
             output.done = true;
             let output_name = output.name.as_ref().unwrap().clone();
+            if let Some((width, height)) = output.size {
+                pandora.set_output_size(&output_name, width, height);
+            }
+            if let Some(rect) = output.logical() {
+                pandora.set_output_layout(&output_name, rect);
+            }
             let config_outputs = &ctx.state.config.outputs;
             let output_config = match config_outputs.iter().find(|oc| oc.name == output_name) {
-                Some(conf) => conf,
+                Some(conf) => conf.clone(),
+                // nothing configured, but `pandora set` put something on it before
+                None if pandora.has_override(&output_name) => OutputConfig { name: output_name.clone(), ..OutputConfig::default() },
                 None => {
                     pandora.log("output-watcher", format!("connect: could not find a config stanza for {output_name}, ignoring"));
                     return;
                 },
             };
-            let mode = match output_config.mode.as_ref() {
-                Some(m) => match m {
-                    RenderMode::Static => RenderMode::Static,
-                    // initial pos of 0 is fine because the agent picks up workspace changes and enforces reflowing
-                    RenderMode::ScrollVertical => RenderMode::ScrollVertical,
-                    RenderMode::ScrollLateral => RenderMode::ScrollLateral,
-                },
-                None => RenderMode::Static,
-            };
+            // initial pos of 0 is fine for the scrolling modes because the agent picks up workspace changes and enforces reflowing
             let cmd = RenderThreadCommand::Render(RenderCommand {
                 output: output_name,
-                image: output_config.image.clone(),
-                mode: mode,
+                image: pandora.resolve_image(&output_config),
+                mode: pandora.resolve_mode(&output_config),
             });
             let _ = ctx.state.pandora.as_ref().unwrap().handle_cmd(&CommandType::Tc(cmd));
         },
         wl_output::Event::Name(name) => output.name = Some(name.into_string().unwrap()),
         _ => (),
     }
-}
\ No newline at end of file
+}
+
+fn xdg_output_cb(ctx: EventCtx<State, ZxdgOutputV1>) {
+    let output = match ctx.state.outputs.iter_mut().find(|o| o.xdg_output == Some(ctx.proxy)) {
+        Some(o) => o,
+        None => return,
+    };
+    match ctx.event {
+        zxdg_output_v1::Event::LogicalPosition(pos) => output.logical_position = Some((pos.x, pos.y)),
+        zxdg_output_v1::Event::LogicalSize(size) => output.logical_size = Some((size.width, size.height)),
+        _ => (),
+    }
+}
@@ -1,7 +1,12 @@
 use ::pandora::pithos::anims::spring::{Spring, SpringParams};
+use ::pandora::pithos::color::ColorSpace;
 use ::pandora::pithos::commands::{RenderCommand, RenderMode, ScrollCommand, RenderThreadCommand};
+use ::pandora::pithos::config::LayerConfig;
 use ::pandora::pithos::error::DaemonError;
-use ::pandora::wayland::render_helpers::{get_wloutput_by_name, OutputMode, RenderState, RenderThreadWaylandState, ScrollState};
+use ::pandora::pithos::misc::mode_scale_to;
+use pandora_ipc::events::Event;
+use ::pandora::pithos::transform::Transform;
+use ::pandora::wayland::render_helpers::{get_wloutput_by_name, layer_position, AnimationState, LayerState, OutputMode, RenderState, RenderThreadWaylandState, ScrollState};
 
 use crate::pandora::Pandora;
 
@@ -9,15 +14,26 @@ use std::ffi::CString;
 use std::sync::mpsc::Receiver;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
-use std::os::fd::OwnedFd;
+use std::os::fd::{AsRawFd, OwnedFd};
 
 use wayrs_client::{Connection, EventCtx, IoMode};
-use wayrs_client::protocol::{WlShm, wl_shm::Format, WlSurface, WlCallback, WlOutput, WlCompositor};
+use wayrs_client::protocol::{WlShm, wl_shm::Format, WlSurface, WlCallback, WlOutput, WlCompositor, WlSubcompositor, WlSeat};
 
+use wayrs_protocols::color_management_v1::{wp_image_description_v1, WpColorManagementSurfaceV1, WpColorManagerV1, WpImageDescriptionV1};
+use wayrs_protocols::color_management_v1::wp_color_manager_v1::RenderIntent;
+use wayrs_protocols::ext_idle_notify_v1::{ext_idle_notification_v1, ExtIdleNotificationV1, ExtIdleNotifierV1};
 use wayrs_protocols::linux_dmabuf_v1::ZwpLinuxDmabufV1;
 use wayrs_protocols::viewporter::{WpViewport, WpViewporter};
 use wayrs_protocols::wlr_layer_shell_unstable_v1::{ZwlrLayerShellV1, ZwlrLayerSurfaceV1, zwlr_layer_surface_v1::Anchor, zwlr_layer_shell_v1::Layer};
 
+// while animating, how long we'll go without checking the command queue
+const ANIMATION_COMMAND_POLL: Duration = Duration::from_millis(100);
+// image descriptions are "eventually" ready; give up on any that aren't after this many roundtrips
+const DESCRIPTION_ROUNDTRIPS: usize = 8;
+// how long the seat goes without input before animations stop advancing. the compositor decides what counts
+// (idle inhibitors, like a fullscreen video, keep it from ever firing)
+const IDLE_PAUSE_AFTER_MS: u32 = 60_000;
+
 // note: output resize/mode-setting changes are not handled here
 // generic output plug/unplug thread handles start/stops for plug events
 
@@ -27,19 +43,32 @@ pub struct RenderThreadWaylandGlobals {
     output_info: OutputMode,
     shm: WlShm, // shared mem singleton
     _dma: ZwpLinuxDmabufV1,
-    _compositor: WlCompositor,
+    compositor: WlCompositor,
+    subcompositor: WlSubcompositor, // for layers
     layer_shell: ZwlrLayerShellV1,
     surface: WlSurface,
-    _viewporter: WpViewporter,
+    viewporter: WpViewporter,
     viewport: WpViewport,
 }
 
+// the surface's wp_color_management_v1 side: one ready image description per color space the compositor takes
+struct SurfaceColor {
+    manager: WpColorManagerV1,
+    surface: WpColorManagementSurfaceV1,
+    descriptions: Vec<(ColorSpace, WpImageDescriptionV1)>,
+    current: Option<ColorSpace>,
+}
+
 pub struct RenderThread {
     name: String,
     receiver: Receiver<RenderThreadCommand>,
     pandora: Arc<Pandora>,
     conn: Connection<RenderThreadWaylandState>,
     globals: Option<RenderThreadWaylandGlobals>,
+    color: Option<SurfaceColor>,
+    // none if the compositor doesn't do ext-idle-notify-v1; animations then only pause when it stops asking for frames
+    idle_notification: Option<ExtIdleNotificationV1>,
+    idle: bool,
     // state below, ough
     render_state: Option<RenderState>,
 }
@@ -54,6 +83,23 @@ fn layer_callback(mut ctx: EventCtx<RenderThreadWaylandState, ZwlrLayerSurfaceV1
     }
 }
 
+fn image_description_cb(ctx: EventCtx<RenderThreadWaylandState, WpImageDescriptionV1>) {
+    let ready = match ctx.event {
+        wp_image_description_v1::Event::Ready(_) => true,
+        wp_image_description_v1::Event::Failed(_) => false,
+        _ => return,
+    };
+    ctx.state.image_descriptions.push((ctx.proxy, ready));
+}
+
+fn idle_notification_cb(ctx: EventCtx<RenderThreadWaylandState, ExtIdleNotificationV1>) {
+    match ctx.event {
+        ext_idle_notification_v1::Event::Idled => ctx.state.idle = Some(true),
+        ext_idle_notification_v1::Event::Resumed => ctx.state.idle = Some(false),
+        _ => (),
+    }
+}
+
 fn initialize_wayland_handles(conn: &mut Connection<RenderThreadWaylandState>, output: String) -> RenderThreadWaylandGlobals {
     let (wl_output, output_info) = get_wloutput_by_name(conn, output);
 
@@ -65,6 +111,7 @@ fn initialize_wayland_handles(conn: &mut Connection<RenderThreadWaylandState>, o
     let layer_shell = conn.bind_singleton::<ZwlrLayerShellV1>(4..=5).unwrap();
     let compositor = conn.bind_singleton::<WlCompositor>(1..=6).unwrap();
     let viewporter = conn.bind_singleton::<WpViewporter>(1..=1).unwrap();
+    let subcompositor = conn.bind_singleton::<WlSubcompositor>(1..=1).unwrap();
 
     let surface = compositor.create_surface(conn);
     let viewport = viewporter.get_viewport(conn, surface);
@@ -73,11 +120,12 @@ fn initialize_wayland_handles(conn: &mut Connection<RenderThreadWaylandState>, o
         output: wl_output,
         shm: shm,
         _dma: dma,
-        _compositor: compositor,
+        compositor,
+        subcompositor,
         layer_shell,
         surface: surface,
         output_info: output_info.mode,
-        _viewporter: viewporter,
+        viewporter,
         viewport: viewport,
     };
 }
@@ -98,6 +146,9 @@ impl RenderThread {
             pandora: pandora,
             conn: conn,
             globals: None,
+            color: None,
+            idle_notification: None,
+            idle: false,
             render_state: None,
         }
     }
@@ -106,7 +157,10 @@ impl RenderThread {
         let cmd = self.receiver.recv().expect("thread exploded while waiting on first command recv");
         match cmd {
             RenderThreadCommand::Render(c) => {
-                self.render(&c).expect("Error initializing render thread");
+                if let Err(e) = self.render(&c) {
+                    self.pandora.emit(Event::ImageFailed { image: c.image.clone(), message: e.to_string() });
+                    panic!("Error initializing render thread: {e:?}");
+                }
             }
             _ => {
                 panic!("invalid initial command received (should be Render");
@@ -128,16 +182,92 @@ impl RenderThread {
     fn end(&mut self) {
         let globals = self.globals.take().unwrap();
         let render_state = self.render_state.take().unwrap();
-        render_state.buffer.destroy(&mut self.conn);
-        render_state.bufpool.destroy(&mut self.conn);
+        self.destroy_buffers(render_state);
         globals.viewport.destroy(&mut self.conn);
-        globals._viewporter.destroy(&mut self.conn);
+        globals.viewporter.destroy(&mut self.conn);
+        globals.subcompositor.destroy(&mut self.conn);
         globals.layer_shell.destroy(&mut self.conn);
         globals._dma.destroy(&mut self.conn);
+        if let Some(color) = self.color.take() {
+            color.descriptions.into_iter().for_each(|(_, d)| d.destroy(&mut self.conn));
+            color.surface.destroy(&mut self.conn);
+            color.manager.destroy(&mut self.conn);
+        }
+        if let Some(notification) = self.idle_notification.take() {
+            notification.destroy(&mut self.conn);
+        }
         globals.surface.destroy(&mut self.conn);
+        self.publish_status();
         self.log("goodbye!".to_string());
     }
 
+    // any seat will do: we only care whether someone's at the machine, not where
+    fn init_idle(&mut self) -> Option<ExtIdleNotificationV1> {
+        let notifier = self.conn.bind_singleton::<ExtIdleNotifierV1>(1..=1).ok()?;
+        let seat = self.conn.bind_singleton::<WlSeat>(1..=1).ok()?;
+        Some(notifier.get_idle_notification_with_cb(&mut self.conn, IDLE_PAUSE_AFTER_MS, seat, idle_notification_cb))
+    }
+
+    // one parametric description per space the outputs thread negotiated, made up front: they have to be ready
+    // before use, and waiting on them now (nothing else in flight yet) beats doing it mid-scroll
+    fn init_color(&mut self) -> Option<SurfaceColor> {
+        let spaces = self.pandora.color_spaces();
+        if spaces.is_empty() {
+            return None;
+        }
+        let surface = self.globals.as_ref()?.surface;
+        let manager = self.conn.bind_singleton::<WpColorManagerV1>(1..=1).ok()?;
+        let color_surface = manager.get_surface(&mut self.conn, surface);
+        let pending: Vec<(ColorSpace, WpImageDescriptionV1)> = spaces.into_iter().map(|space| {
+            let creator = manager.create_parametric_creator(&mut self.conn);
+            creator.set_primaries_named(&mut self.conn, space.primaries());
+            creator.set_tf_named(&mut self.conn, space.transfer());
+            (space, creator.create_with_cb(&mut self.conn, image_description_cb))
+        }).collect();
+
+        let mut state = RenderThreadWaylandState::default();
+        for _ in 0..DESCRIPTION_ROUNDTRIPS {
+            if state.image_descriptions.len() == pending.len() {
+                break;
+            }
+            self.conn.blocking_roundtrip().unwrap();
+            self.conn.dispatch_events(&mut state);
+        }
+        let mut descriptions = Vec::new();
+        for (space, description) in pending {
+            match state.image_descriptions.iter().find(|(d, _)| *d == description) {
+                Some((_, true)) => descriptions.push((space, description)),
+                _ => {
+                    self.log(format!("compositor wouldn't describe {space:?}; it'll be shown untagged"));
+                    description.destroy(&mut self.conn);
+                },
+            }
+        }
+        Some(SurfaceColor { manager, surface: color_surface, descriptions, current: None })
+    }
+
+    // takes effect on the next commit
+    fn tag_surface(&mut self, space: ColorSpace) {
+        let Some(color) = self.color.as_mut() else {
+            return;
+        };
+        if color.current == Some(space) {
+            return;
+        }
+        match color.descriptions.iter().find(|(s, _)| *s == space) {
+            Some((_, description)) => {
+                color.surface.set_image_description(&mut self.conn, *description, RenderIntent::Perceptual);
+                color.current = Some(space);
+            },
+            None => {
+                color.surface.unset_image_description(&mut self.conn);
+                color.current = None;
+            },
+        }
+        let tagged = color.current;
+        self.verbose(format!("surface tagged as {tagged:?}"));
+    }
+
     fn set_layer_shell_on_surface(&mut self) {
         let globals = self.globals.as_ref().unwrap();
         let width = globals.output_info.width;
@@ -157,43 +287,93 @@ impl RenderThread {
     // todo: generally rewrite the buffer management >.<
     // will eventually want to / need to support more pixel formats (at least for HDR)....
     // thankfully I have an hdr monitor :) ... but for now.... rgba8. that's fine.
+    // surfaces do get tagged with the image's color space (see init_color), so wide gamut images show right;
+    // pq ones want a 10 bit format to not band.
 
     fn render(&mut self, cmd: &RenderCommand) -> Result<(), DaemonError> {
         if self.globals.is_none() {
             self.globals = Some(initialize_wayland_handles(&mut self.conn, cmd.output.clone()));
+            self.color = self.init_color();
+            self.idle_notification = self.init_idle();
             self.set_layer_shell_on_surface();
         }
-        if self.render_state.is_some() { // could try to transition old state to new state/animate, maybe.
-            let render_state = self.render_state.take().unwrap();
-            render_state.buffer.destroy(&mut self.conn);
-            render_state.bufpool.destroy(&mut self.conn);
-            // apparently explodes a bit? image updates, but then scrolling no longer works.
-        }
-        let globals = self.globals.take().unwrap();
-        let file = tempfile::tempfile().expect("creating tempfile for shared mem failed");
-        let (output_width, output_height) = (globals.output_info.width as u32, globals.output_info.height as u32);
+        let (output_width, output_height) = {
+            let info = &self.globals.as_ref().unwrap().output_info;
+            (info.width as u32, info.height as u32)
+        };
 
         // i decided that downscaling to minimize resource footprint while maximizing quality is mandatory
         // easier to reason about
-        let scale_to = match cmd.mode {
-            RenderMode::Static => Some((Some(output_width), Some(output_height))),
-            RenderMode::ScrollVertical => Some((Some(output_width), None)),
-            RenderMode::ScrollLateral => Some((None, Some(output_height)))
-        };
+        let scale_to = Some(mode_scale_to(cmd.mode, output_width, output_height));
 
-        let (img_width, img_height) = self.pandora.read_img_to_file(&cmd.image, &file, scale_to)?;
+        // threads showing the same image at the same size share the backing memory
+        let transform = self.pandora.output_transform(&cmd.output, &cmd.image);
+        let effects = self.pandora.output_effects(&cmd.output);
+        let shm = self.pandora.acquire_buffer(&cmd.image, scale_to, Format::Argb8888, &transform, &effects)?;
+        let (img_width, img_height) = (shm.key.width, shm.key.height);
         let bytes_per_row: i32 = img_width as i32 * 4;
-        let total_bytes: i32 = bytes_per_row * img_height as i32;
+        let frame_bytes: i32 = bytes_per_row * img_height as i32;
+        // a pool's size is an i32, so a long animation at a big resolution can't be mapped whole. it's still a
+        // perfectly good image, so show its first frame rather than nothing
+        let (frames, total_bytes) = match i32::try_from(frame_bytes as i64 * shm.frames() as i64) {
+            Ok(total) => (shm.frames(), total),
+            Err(_) => {
+                self.log(format!("{}: {} frames at {img_width} x {img_height} is too big for a single shm pool; showing just the first", cmd.image, shm.frames()));
+                (1, frame_bytes)
+            }
+        };
 
         if img_width < output_width || img_height < output_height {
             self.log(format!("image scaled to {img_width} x {img_height}, but output is {output_width} by {output_height}.\n   Try static mode for this image, as it's maybe insufficient for the desired mode :("));
+            self.pandora.release_buffer(&shm);
             return Err(DaemonError::LogicalError);
         }
+
+        // nothing past here can fail, so only now let go of what's on screen: a render that errors out above
+        // leaves the old wallpaper (and its state) as it was
+        // re-rendering in the same scroll mode (file changed on disk, config reload) shouldn't jump back to the top
+        let mut carried_pos = 0;
+        if self.render_state.is_some() { // could try to transition old state to new state/animate, maybe.
+            let render_state = self.render_state.take().unwrap();
+            if render_state.mode == cmd.mode && let Some(scroll) = render_state.scrolling {
+                carried_pos = scroll.end_pos;
+            }
+            self.destroy_buffers(render_state);
+            // apparently explodes a bit? image updates, but then scrolling no longer works.
+        }
+        let globals = self.globals.take().unwrap();
         self.verbose(format!("file loaded and scaled to {img_width} x {img_height}"));
+        let layers = self.create_layers(&globals, &self.pandora.output_layers(&cmd.output), scale_to, cmd.mode);
+        carried_pos = match cmd.mode {
+            RenderMode::Static => 0,
+            RenderMode::ScrollVertical => carried_pos.min(img_height - output_height),
+            RenderMode::ScrollLateral => carried_pos.min(img_width - output_width),
+        };
 
-        let pool = globals.shm.create_pool(&mut self.conn, OwnedFd::from(file.try_clone().unwrap()), total_bytes);
+        self.tag_surface(self.pandora.image_color(&cmd.image));
+        let pool = globals.shm.create_pool(&mut self.conn, OwnedFd::from(shm.file.try_clone().unwrap()), total_bytes);
         let buf = pool.create_buffer(&mut self.conn, 0, img_width as i32, img_height as i32, bytes_per_row, Format::Argb8888 );
         globals.surface.attach(&mut self.conn, Some(buf), 0, 0); //hardcoded 0s l0l
+
+        let animation = match frames {
+            1 => None,
+            frames => {
+                self.verbose(format!("animated: {frames} frames"));
+                let mut buffers = vec![buf];
+                for i in 1..frames {
+                    buffers.push(pool.create_buffer(&mut self.conn, frame_bytes * i as i32,
+                        img_width as i32, img_height as i32, bytes_per_row, Format::Argb8888));
+                }
+                globals.surface.frame_with_cb(&mut self.conn, animation_frame_callback);
+                Some(AnimationState {
+                    frames: buffers,
+                    delays: shm.delays.clone(),
+                    current: 0,
+                    due: Instant::now() + shm.delays[0],
+                    awaiting_frame: true,
+                })
+            },
+        };
         
         self.verbose(format!("cropping surface view to {output_width} x {output_height}"));
 
@@ -218,14 +398,14 @@ impl RenderThread {
             }
             RenderMode::ScrollVertical => {
                 Some(ScrollState {
-                    start_pos: 0,
-                    current_pos: 0,
-                    end_pos: 0,
+                    start_pos: carried_pos,
+                    current_pos: carried_pos,
+                    end_pos: carried_pos,
                     anim_start: Instant::now(),
                     anim_duration: Duration::ZERO,
                     anim: Spring {
-                        from: 0 as f64,
-                        to: 0 as f64,
+                        from: carried_pos as f64,
+                        to: carried_pos as f64,
                         initial_velocity: 0.0,
                         params: SpringParams::default(),
                     },
@@ -234,14 +414,14 @@ impl RenderThread {
             },
             RenderMode::ScrollLateral => {
                 Some(ScrollState {
-                    start_pos: 0,
-                    current_pos: 0,
-                    end_pos: 0,
+                    start_pos: carried_pos,
+                    current_pos: carried_pos,
+                    end_pos: carried_pos,
                     anim_start: Instant::now(),
                     anim_duration: Duration::ZERO,
                     anim: Spring {
-                        from: 0 as f64,
-                        to: 0 as f64,
+                        from: carried_pos as f64,
+                        to: carried_pos as f64,
                         initial_velocity: 0.0,
                         params: SpringParams::default(),
                     },
@@ -253,15 +433,17 @@ impl RenderThread {
         self.globals = Some(globals);
         self.render_state = Some(RenderState {
             mode: cmd.mode,
-            _img_path: cmd.image.clone(),
-            _buf_file: file,
+            img_path: cmd.image.clone(),
+            shm,
             buffer: buf,
             bufpool: pool,
+            animation,
             scrolling: scroll_state,
             crop_width: output_width,
             crop_height: output_height,
             orig_width: img_width,
             orig_height: img_height,
+            layers,
         });
 
         if scroll_state.is_some() {
@@ -270,9 +452,16 @@ impl RenderThread {
 
         globals.surface.commit(&mut self.conn);
         self.conn.blocking_roundtrip().unwrap();
+        self.publish_status();
+        self.pandora.emit(Event::WallpaperChanged { output: self.name.clone(), image: cmd.image.clone(), mode: cmd.mode });
         Ok(())
     }
 
+    // for `pandora status`
+    fn publish_status(&self) {
+        self.pandora.set_render_status(&self.name, self.render_state.as_ref().map(|s| s.status()));
+    }
+
     fn draw_loop(&mut self) {
         loop {
             // this is still kinda gross. needs rewriting still.
@@ -288,19 +477,135 @@ impl RenderThread {
 
             self.conn.dispatch_events(&mut dispatch_state);
             self.render_state = dispatch_state.render_state;
+            if let Some(idle) = dispatch_state.idle && idle != self.idle {
+                self.idle = idle;
+                if self.render_state.as_ref().is_some_and(|r| r.animation.is_some()) {
+                    self.verbose(format!("animation {}", if idle { "paused, seat is idle" } else { "resumed" }));
+                }
+            }
+            if dispatch_state.scroll_finished
+                && let Some(scroll) = self.render_state.as_ref().and_then(|r| r.scrolling.as_ref()) {
+                self.pandora.emit(Event::TransitionFinished { output: self.name.clone(), position: scroll.current_pos });
+            }
 
             self.handle_inbound_commands();
+            self.step_animation();
 
             if received_events.is_err() { // did not process any animation commands this tick; block on command queue lazy style
                 let scroll_state = self.render_state.as_ref().unwrap().scrolling.as_ref();
                 if scroll_state.is_none() || !is_animating(scroll_state.unwrap()) {
-                    // not animating currently - BLOCK AND WAIT HERE
-                    self.handle_cmd(&self.receiver.recv().expect("thread exploded during blocking read on inbound commands"));
+                    match self.animation_wait() {
+                        // animated image: sleep until the next frame is due, or the compositor gets back to us
+                        Some(timeout) => self.wait_for_events(timeout),
+                        // not animating currently - BLOCK AND WAIT HERE
+                        None => self.handle_cmd(&self.receiver.recv().expect("thread exploded during blocking read on inbound commands")),
+                    }
                 }
             }
         }
     }
 
+    fn destroy_buffers(&mut self, render_state: RenderState) {
+        match render_state.animation {
+            // the first frame is `buffer`
+            Some(animation) => animation.frames.into_iter().for_each(|b| b.destroy(&mut self.conn)),
+            None => render_state.buffer.destroy(&mut self.conn),
+        }
+        render_state.bufpool.destroy(&mut self.conn);
+        self.pandora.release_buffer(&render_state.shm);
+        for layer in render_state.layers {
+            layer.buffer.destroy(&mut self.conn);
+            layer.bufpool.destroy(&mut self.conn);
+            layer.viewport.destroy(&mut self.conn);
+            layer.subsurface.destroy(&mut self.conn);
+            layer.surface.destroy(&mut self.conn);
+            self.pandora.release_buffer(&layer.shm);
+        }
+    }
+
+    // each layer gets scaled the same way the output's image was, onto its own subsurface stacked above the last.
+    // subsurfaces are synced by default, so whatever they're told only shows up with the next commit of ours
+    fn create_layers(&mut self, globals: &RenderThreadWaylandGlobals, layers: &[LayerConfig], scale_to: Option<(Option<u32>, Option<u32>)>, mode: RenderMode) -> Vec<LayerState> {
+        let (output_width, output_height) = (globals.output_info.width as u32, globals.output_info.height as u32);
+        let mut created = Vec::new();
+        for layer in layers {
+            let shm = match self.pandora.acquire_buffer(&layer.image, scale_to, Format::Argb8888, &Transform::default(), &layer.effects()) {
+                Ok(shm) => shm,
+                Err(e) => {
+                    self.log(format!("skipping layer {}: {e:?}", layer.image));
+                    continue;
+                },
+            };
+            let (width, height) = (shm.key.width, shm.key.height);
+            if width < output_width || height < output_height {
+                self.log(format!("skipping layer {}: scaled to {width} x {height}, which doesn't cover the output", layer.image));
+                self.pandora.release_buffer(&shm);
+                continue;
+            }
+            let surface = globals.compositor.create_surface(&mut self.conn);
+            let subsurface = globals.subcompositor.get_subsurface(&mut self.conn, surface, globals.surface);
+            let viewport = globals.viewporter.get_viewport(&mut self.conn, surface);
+            // only the first frame of animated ones
+            let frame_bytes = width as i32 * 4 * height as i32;
+            let bufpool = globals.shm.create_pool(&mut self.conn, OwnedFd::from(shm.file.try_clone().unwrap()), frame_bytes);
+            let buffer = bufpool.create_buffer(&mut self.conn, 0, width as i32, height as i32, width as i32 * 4, Format::Argb8888);
+            surface.attach(&mut self.conn, Some(buffer), 0, 0);
+            viewport.set_destination(&mut self.conn, globals.output_info.width, globals.output_info.height);
+            if mode == RenderMode::Static {
+                viewport.set_source(&mut self.conn,
+                    ((width - output_width) / 2).into(), ((height - output_height) / 2).into(),
+                    output_width.into(), output_height.into(),
+                );
+            }
+            surface.commit(&mut self.conn);
+            self.verbose(format!("layer {} scaled to {width} x {height}", layer.image));
+            created.push(LayerState { surface, subsurface, viewport, shm, buffer, bufpool, scroll_factor: layer.scroll_factor(), width, height });
+        }
+        created
+    }
+
+    // puts the next frame up if it's due and the compositor has shown the last one. nothing is committed (so no
+    // frame callbacks asked for) while the seat is idle
+    fn step_animation(&mut self) {
+        if self.idle {
+            return;
+        }
+        let (Some(globals), Some(render_state)) = (self.globals.as_ref(), self.render_state.as_mut()) else {
+            return;
+        };
+        let animation = match render_state.animation.as_mut() {
+            Some(a) if !a.awaiting_frame && Instant::now() >= a.due => a,
+            _ => return,
+        };
+        animation.current = (animation.current + 1) % animation.frames.len();
+        let delay = animation.delays[animation.current];
+        // after a pause (or a slow frame) carry on from now rather than racing to catch up
+        animation.due = (animation.due + delay).max(Instant::now());
+        animation.awaiting_frame = true;
+        globals.surface.attach(&mut self.conn, Some(animation.frames[animation.current]), 0, 0);
+        globals.surface.damage(&mut self.conn, 0, 0, i32::MAX, i32::MAX);
+        globals.surface.frame_with_cb(&mut self.conn, animation_frame_callback);
+        globals.surface.commit(&mut self.conn);
+    }
+
+    // None if there's nothing animating
+    fn animation_wait(&self) -> Option<Duration> {
+        let animation = self.render_state.as_ref()?.animation.as_ref()?;
+        // just keep an ear out for commands and the seat waking up
+        if animation.awaiting_frame || self.idle {
+            return Some(ANIMATION_COMMAND_POLL);
+        }
+        Some(animation.due.saturating_duration_since(Instant::now()).min(ANIMATION_COMMAND_POLL))
+    }
+
+    // blocks until the wayland socket has something for us or `timeout` runs out, then picks up any commands
+    fn wait_for_events(&mut self, timeout: Duration) {
+        let mut fds = [libc::pollfd { fd: self.conn.as_raw_fd(), events: libc::POLLIN, revents: 0 }];
+        // SAFETY: fds is a valid array of one pollfd for the duration of the call
+        unsafe { libc::poll(fds.as_mut_ptr(), 1, timeout.as_millis() as libc::c_int) };
+        self.handle_inbound_commands();
+    }
+
     // returns true if it's time to exit e.g. received stop
     fn handle_inbound_commands(&mut self) {
         loop {
@@ -314,7 +619,11 @@ impl RenderThread {
     fn handle_cmd(&mut self, cmd: &RenderThreadCommand) {
         match cmd {
             RenderThreadCommand::Render(c) => {
-                self.render(c).expect("error handling render command");
+                // whatever was up before stays up
+                if let Err(e) = self.render(c) {
+                    self.log(format!("could not render {}: {e:?}", c.image));
+                    self.pandora.emit(Event::ImageFailed { image: c.image.clone(), message: e.to_string() });
+                }
             }
             RenderThreadCommand::Stop(_) => {
                 self.end();
@@ -369,6 +678,7 @@ impl RenderThread {
 
         render_state.scrolling = Some(scroll_state);
         self.render_state = Some(render_state);
+        self.publish_status();
 
         self.debug(format!("scrolling from {} to {}, expected duration {:?} (was already scrolling: {})", scroll_state.start_pos, scroll_state.end_pos, scroll_state.anim_duration, is_already_scrolling));
 
@@ -444,6 +754,32 @@ fn do_scroll_step(conn: &mut Connection<RenderThreadWaylandState>,
             );
         },
     };
+    // every layer moves off the same spring, so they all land together
+    let range = match render_state.mode {
+        RenderMode::Static => 0,
+        RenderMode::ScrollVertical => render_state.orig_height - render_state.crop_height,
+        RenderMode::ScrollLateral => render_state.orig_width - render_state.crop_width,
+    };
+    for layer in &render_state.layers {
+        match render_state.mode {
+            RenderMode::Static => continue,
+            RenderMode::ScrollVertical => {
+                let pos = layer_position(next_pos, range, layer.scroll_factor, layer.height - render_state.crop_height);
+                layer.viewport.set_source(conn,
+                    wayrs_client::Fixed::ZERO, pos.into(),
+                    render_state.crop_width.into(), render_state.crop_height.into(),
+                );
+            },
+            RenderMode::ScrollLateral => {
+                let pos = layer_position(next_pos, range, layer.scroll_factor, layer.width - render_state.crop_width);
+                layer.viewport.set_source(conn,
+                    pos.into(), wayrs_client::Fixed::ZERO,
+                    render_state.crop_width.into(), render_state.crop_height.into(),
+                );
+            },
+        }
+        layer.surface.commit(conn);
+    }
     let mut scroll_state = render_state.scrolling.take().unwrap();
     scroll_state.current_pos = next_pos;
     render_state.scrolling = Some(scroll_state);
@@ -463,10 +799,19 @@ fn frame_callback(ctx: EventCtx<RenderThreadWaylandState, WlCallback>) {
         do_scroll_step(ctx.conn, &mut render_state,
             &wl_state.viewport.unwrap(), &wl_state.output_info.unwrap(), &wl_state.surface.unwrap(), new_pos
         );
+    } else {
+        wl_state.scroll_finished = true;
     }
     wl_state.render_state = Some(render_state);
 }
 
+// outputs that are off never get here, which pauses playback on them; idle ones are paused in step_animation
+fn animation_frame_callback(ctx: EventCtx<RenderThreadWaylandState, WlCallback>) {
+    if let Some(animation) = ctx.state.render_state.as_mut().and_then(|r| r.animation.as_mut()) {
+        animation.awaiting_frame = false;
+    }
+}
+
 fn is_animating(state: &ScrollState) -> bool {
     return (Instant::now() - state.anim_start) < state.anim_duration;
 }
\ No newline at end of file
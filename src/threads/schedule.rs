@@ -0,0 +1,131 @@
+use ::pandora::pithos::commands::DaemonCommand;
+use ::pandora::pithos::error::DaemonError;
+use ::pandora::pithos::schedule::current_entry;
+use pandora_ipc::status::ChangeCause;
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, Weak, mpsc::{channel, Receiver, RecvTimeoutError, Sender}};
+use std::thread;
+use std::time::Duration;
+
+use chrono::Local;
+
+use crate::pandora::Pandora;
+
+// sleeping is on the monotonic clock, which stops during suspend and doesn't care about the wall clock changing;
+// re-check at least this often so we never end up hours behind
+const MAX_SLEEP: Duration = Duration::from_secs(60);
+// start decoding the next entry's image this long before it's due
+const PRELOAD_AHEAD: Duration = Duration::from_secs(30);
+
+// switches outputs between schedule entries as the day goes on
+pub struct Scheduler {
+    cmd_queue: Arc<Mutex<Receiver<DaemonCommand>>>,
+    pub queue: Sender<DaemonCommand>,
+}
+
+impl Scheduler {
+    pub fn new() -> Arc<Scheduler> {
+        let (send, recv) = channel::<DaemonCommand>();
+        Arc::new(Scheduler {
+            cmd_queue: Arc::new(Mutex::new(recv)),
+            queue: send,
+        })
+    }
+
+    pub fn start(&self, weak: Weak<Pandora>) {
+        let pandora = weak.upgrade().unwrap();
+        let cmd_queue = self.cmd_queue.clone();
+        match thread::Builder::new().name("scheduler".to_string())
+        .spawn(move || {
+            run(pandora, cmd_queue);
+        }) {
+            Ok(_) => (),
+            Err(e) => panic!("could not spawn scheduler thread: {e:?}"),
+        }
+    }
+}
+
+struct Tick {
+    // key: output name. what the schedule wants on screen right now
+    current: HashMap<String, String>,
+    // the soonest upcoming entry change, and the image it switches to
+    next: Option<(Duration, String)>,
+}
+
+fn tick(pandora: &Pandora) -> Result<Tick, DaemonError> {
+    let config = pandora.config()?;
+    let now = Local::now();
+    let location = config.location.as_ref();
+    let mut tick = Tick { current: HashMap::new(), next: None };
+    for output in &config.outputs {
+        let entries = match pandora.schedule_entries(output) {
+            Some(e) => e,
+            None => continue,
+        };
+        let (current, next_at) = current_entry(&entries, location, &now);
+        if let Some(entry) = current {
+            tick.current.insert(output.name.clone(), entry.image.clone());
+        }
+        if let Some(next_at) = next_at
+            && let (Some(entry), _) = current_entry(&entries, location, &next_at) {
+            let wait = (next_at - now).to_std().unwrap_or_default();
+            if tick.next.as_ref().is_none_or(|(w, _)| wait < *w) {
+                tick.next = Some((wait, entry.image.clone()));
+            }
+        }
+    }
+    Ok(tick)
+}
+
+fn run(pandora: Arc<Pandora>, cmd_queue: Arc<Mutex<Receiver<DaemonCommand>>>) {
+    let channel = match cmd_queue.lock() {
+        Ok(channel) => channel,
+        Err(e) => {
+            pandora.log("scheduler", format!("could not take command queue: {e:?}"));
+            return;
+        },
+    };
+    // outputs come up showing whatever resolve_image says is current, so only changes from here on need sending
+    let mut shown: HashMap<String, String> = HashMap::new();
+    let mut primed = false;
+    let mut preloaded: Option<String> = None;
+    loop {
+        let tick = match tick(&pandora) {
+            Ok(t) => t,
+            Err(e) => {
+                pandora.log("scheduler", format!("could not read config: {e:?}"));
+                return;
+            }
+        };
+        for (output, image) in &tick.current {
+            if primed && shown.get(output) != Some(image) {
+                pandora.log("scheduler", format!("schedule moving {output} to {image}"));
+                pandora.show_image(output, image.clone(), ChangeCause::Schedule);
+            }
+        }
+        shown = tick.current;
+        primed = true;
+
+        let mut timeout = MAX_SLEEP;
+        if let Some((wait, image)) = tick.next {
+            let wake = if preloaded.as_ref() == Some(&image) {
+                // + a hair so we land just after the boundary rather than just before it
+                wait + Duration::from_millis(50)
+            } else if wait <= PRELOAD_AHEAD {
+                pandora.preload_image(image.clone());
+                preloaded = Some(image);
+                wait + Duration::from_millis(50)
+            } else {
+                wait - PRELOAD_AHEAD
+            };
+            timeout = timeout.min(wake);
+        }
+        match channel.recv_timeout(timeout) {
+            // the niri agent re-renders anything a config reload changed, so just resync with the new schedule
+            Ok(DaemonCommand::ReloadConfig(_)) => primed = false,
+            Ok(_) | Err(RecvTimeoutError::Timeout) => (),
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+    }
+}
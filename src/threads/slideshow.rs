@@ -0,0 +1,57 @@
+use ::pandora::pithos::commands::DaemonCommand;
+
+use std::sync::{Arc, Mutex, Weak, mpsc::{channel, Receiver, RecvTimeoutError, Sender}};
+use std::thread;
+use std::time::Duration;
+
+use crate::pandora::Pandora;
+
+// nothing to rotate; still wake up now and then in case a reload added a slideshow and we missed the poke
+const IDLE_WAKEUP: Duration = Duration::from_secs(60);
+
+// advances slideshows when their interval runs out, and handles next/prev/pause from the cli
+pub struct SlideshowHandler {
+    cmd_queue: Arc<Mutex<Receiver<DaemonCommand>>>,
+    pub queue: Sender<DaemonCommand>,
+}
+
+impl SlideshowHandler {
+    pub fn new() -> Arc<SlideshowHandler> {
+        let (send, recv) = channel::<DaemonCommand>();
+        Arc::new(SlideshowHandler {
+            cmd_queue: Arc::new(Mutex::new(recv)),
+            queue: send,
+        })
+    }
+
+    pub fn start(&self, weak: Weak<Pandora>) {
+        let pandora = weak.upgrade().unwrap();
+        let cmd_queue = self.cmd_queue.clone();
+        match thread::Builder::new().name("slideshows".to_string())
+        .spawn(move || {
+            run(pandora, cmd_queue);
+        }) {
+            Ok(_) => (),
+            Err(e) => panic!("could not spawn slideshow thread: {e:?}"),
+        }
+    }
+}
+
+fn run(pandora: Arc<Pandora>, cmd_queue: Arc<Mutex<Receiver<DaemonCommand>>>) {
+    let channel = match cmd_queue.lock() {
+        Ok(channel) => channel,
+        Err(e) => {
+            pandora.log("slideshows", format!("could not take command queue: {e:?}"));
+            return;
+        },
+    };
+    loop {
+        let timeout = pandora.next_slide_in().unwrap_or(IDLE_WAKEUP);
+        match channel.recv_timeout(timeout) {
+            Ok(DaemonCommand::Slideshow(c)) => pandora.slideshow_command(&c),
+            Ok(_) => (), // config reloads etc. just mean the deadline gets recomputed
+            Err(RecvTimeoutError::Timeout) => pandora.advance_due_slideshows(),
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+    }
+}
@@ -1,6 +1,7 @@
-use crate::pithos::{anims::spring::Spring, commands::RenderMode};
+use crate::pithos::{anims::spring::Spring, cache::buffers::SharedBuffer, commands::RenderMode};
+use pandora_ipc::status::RenderStatus;
 
-use std::fs::File;
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 
 use wayrs_client::{Connection, EventCtx, IoMode};
@@ -9,6 +10,8 @@ use wayrs_client::protocol::wl_output::{self, WlOutput};
 use wayrs_client::protocol::wl_surface::WlSurface;
 use wayrs_client::protocol::WlBuffer;
 use wayrs_client::protocol::WlShmPool;
+use wayrs_client::protocol::WlSubsurface;
+use wayrs_protocols::color_management_v1::WpImageDescriptionV1;
 use wayrs_protocols::viewporter::WpViewport;
 
 
@@ -21,6 +24,10 @@ pub struct RenderThreadWaylandState {
     pub viewport: Option<WpViewport>,
     pub surface: Option<WlSurface>,
     pub output_info: Option<OutputMode>,
+    // image descriptions that have heard back from the compositor: (description, ready)
+    pub image_descriptions: Vec<(WpImageDescriptionV1, bool)>,
+    pub scroll_finished: bool, // set by the frame callback that sees a scroll animation come to rest
+    pub idle: Option<bool>, // set by the idle notification when the seat goes idle (true) or wakes back up (false)
 }
 
 #[derive(Copy, Clone)]
@@ -34,17 +41,69 @@ pub struct ScrollState {
     pub _frame_count: u32,
 }
 
+// frames of an animated image, each its own wl_buffer into the same pool
+pub struct AnimationState {
+    pub frames: Vec<WlBuffer>,
+    pub delays: Arc<Vec<Duration>>,
+    pub current: usize,
+    pub due: Instant, // when the next frame should go up
+    // set while the compositor hasn't shown our last frame yet. it stops sending frame callbacks for outputs that are
+    // off or surfaces nobody can see, which pauses playback there; an idle seat pauses it on the render thread's side
+    pub awaiting_frame: bool,
+}
+
+// a `layer` over the output's image: its own subsurface + viewport, following the same scroll at its own rate
+pub struct LayerState {
+    pub surface: WlSurface,
+    pub subsurface: WlSubsurface,
+    pub viewport: WpViewport,
+    pub shm: SharedBuffer,
+    pub buffer: WlBuffer,
+    pub bufpool: WlShmPool,
+    pub scroll_factor: f64,
+    pub width: u32,
+    pub height: u32,
+}
+
+// where a layer that can scroll `layer_range` sits while the output's image is `pos` into its own `range`.
+// the same fraction of the way through, times the layer's factor
+pub fn layer_position(pos: u32, range: u32, scroll_factor: f64, layer_range: u32) -> u32 {
+    if range == 0 {
+        return 0;
+    }
+    let progress = pos as f64 / range as f64;
+    ((progress * scroll_factor * layer_range as f64).round() as u32).min(layer_range)
+}
+
 pub struct RenderState {
     pub mode: RenderMode,
-    pub _img_path: String,
-    pub _buf_file: File,
-    pub buffer: WlBuffer,
+    pub img_path: String,
+    pub shm: SharedBuffer, // keeps the shared fd alive; hand back to pandora when done
+    pub buffer: WlBuffer, // the first frame, for animations
     pub bufpool: WlShmPool,
+    pub animation: Option<AnimationState>,
     pub scrolling: Option<ScrollState>,
     pub crop_width: u32,
     pub crop_height: u32,
     pub orig_width: u32,
     pub orig_height: u32,
+    pub layers: Vec<LayerState>, // bottom to top
+}
+
+impl RenderState {
+    pub fn status(&self) -> RenderStatus {
+        RenderStatus {
+            image: self.img_path.clone(),
+            mode: self.mode,
+            width: self.crop_width,
+            height: self.crop_height,
+            image_width: self.orig_width,
+            image_height: self.orig_height,
+            frames: self.animation.as_ref().map_or(1, |a| a.frames.len()),
+            scroll_position: self.scrolling.as_ref().map(|s| s.end_pos),
+            layers: self.layers.len(),
+        }
+    }
 }
 
 